@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use bevy_snake::food_strategy::{FoodStrategy, UniformStrategy};
+use bevy_snake::game::{free_positions, random_free_position, ArenaSize, GameRng, Position};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::prelude::*;
+
+const SIZE: u32 = 100;
+const FILLS: [f32; 3] = [0.1, 0.5, 0.95];
+
+fn taken_positions(arena: &ArenaSize, fill: f32) -> HashSet<Position> {
+  let mut positions = (0..arena.height as i32)
+    .flat_map(|y| (0..arena.width as i32).map(move |x| Position::new(x, y)))
+    .collect::<Vec<Position>>();
+  positions.shuffle(&mut StdRng::seed_from_u64(0));
+  let count = (positions.len() as f32 * fill) as usize;
+  positions.into_iter().take(count).collect()
+}
+
+// What placement used to do: keep guessing tiles until a free one comes up,
+// which gets slow as the board fills up.
+fn rejection_sampling(
+  rng: &mut GameRng,
+  arena: &ArenaSize,
+  taken_positions: &HashSet<Position>,
+) -> Option<Position> {
+  if taken_positions.len() >= (arena.width * arena.height) as usize { return None; }
+  loop {
+    let position = Position::new(
+      rng.0.gen_range(0..arena.width as i32),
+      rng.0.gen_range(0..arena.height as i32),
+    );
+    if !taken_positions.contains(&position) {
+      return Some(position);
+    }
+  }
+}
+
+fn food_placement(c: &mut Criterion) {
+  let arena = ArenaSize { width: SIZE, height: SIZE };
+  let mut group = c.benchmark_group("food_placement");
+  for &fill in FILLS.iter() {
+    let taken = taken_positions(&arena, fill);
+    let label = format!("{}%", (fill * 100.0) as u32);
+    group.bench_with_input(BenchmarkId::new("free_cells", &label), &taken, |b, taken| {
+      let mut rng = GameRng::from_seed(0);
+      b.iter(|| random_free_position(&mut rng, black_box(&arena), black_box(taken)));
+    });
+    // What `food_spawner` does with the default strategy.
+    group.bench_with_input(BenchmarkId::new("spawner", &label), &taken, |b, taken| {
+      let mut rng = GameRng::from_seed(0);
+      let mut strategy = UniformStrategy;
+      b.iter(|| {
+        let free = free_positions(black_box(&arena), black_box(taken));
+        strategy.pick(&free, &mut rng.0)
+      });
+    });
+    group.bench_with_input(BenchmarkId::new("rejection", &label), &taken, |b, taken| {
+      let mut rng = GameRng::from_seed(0);
+      b.iter(|| rejection_sampling(&mut rng, black_box(&arena), black_box(taken)));
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, food_placement);
+criterion_main!(benches);
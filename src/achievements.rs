@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::game::*;
+
+pub const ACHIEVEMENTS_FILE: &str = "achievements.txt";
+
+// What the current run has to reach for an achievement to unlock.
+pub enum Condition {
+  Length(usize),
+  Score(u32),
+  SurviveSeconds(f32),
+  Eat(FoodKind),
+}
+
+pub struct Achievement {
+  pub id: &'static str,
+  pub name: &'static str,
+  pub condition: Condition,
+}
+
+impl Achievement {
+  pub const fn new(id: &'static str, name: &'static str, condition: Condition) -> Self {
+    Self { id, name, condition }
+  }
+}
+
+// Every achievement there is. The ids are what's saved, so they shouldn't change.
+pub const ACHIEVEMENTS: &[Achievement] = &[
+  Achievement::new("length_10", "Reach length 10", Condition::Length(10)),
+  Achievement::new("length_25", "Reach length 25", Condition::Length(25)),
+  Achievement::new("score_100", "Score 100 points", Condition::Score(100)),
+  Achievement::new("survive_60", "Survive 60s", Condition::SurviveSeconds(60.0)),
+  Achievement::new("eat_bonus", "Eat bonus food", Condition::Eat(FoodKind::Bonus)),
+  Achievement::new("eat_big", "Eat big food", Condition::Eat(FoodKind::Big)),
+];
+
+// The ids of every achievement unlocked so far, across runs.
+#[derive(Default)]
+pub struct Achievements {
+  pub unlocked: HashSet<String>,
+}
+
+pub struct AchievementUnlocked {
+  pub id: &'static str,
+}
+
+pub fn achievements_path() -> PathBuf {
+  std::env::current_exe()
+    .ok()
+    .and_then(|exe| exe.parent().map(|dir| dir.join(ACHIEVEMENTS_FILE)))
+    .unwrap_or_else(|| PathBuf::from(ACHIEVEMENTS_FILE))
+}
+
+// One id per line. A missing file means nothing's been unlocked yet.
+pub fn load_achievements(path: &Path) -> Achievements {
+  let unlocked = fs::read_to_string(path)
+    .map(|contents| {
+      contents.lines().map(str::trim).filter(|id| !id.is_empty()).map(String::from).collect()
+    })
+    .unwrap_or_default();
+  Achievements { unlocked }
+}
+
+pub fn save_achievements(path: &Path, achievements: &Achievements) -> io::Result<()> {
+  let mut ids = achievements.unlocked.iter().map(String::as_str).collect::<Vec<&str>>();
+  ids.sort_unstable();
+  fs::write(path, ids.join("\n"))
+}
+
+pub fn check_achievements(
+  stats: Res<GameStats>,
+  survival_time: Res<SurvivalTime>,
+  mut score_reader: EventReader<ScoreEvent>,
+  mut achievements: ResMut<Achievements>,
+  mut unlocked_writer: EventWriter<AchievementUnlocked>,
+) {
+  let eaten = score_reader.iter().map(|event| event.kind).collect::<Vec<FoodKind>>();
+  let met = |condition: &Condition| match *condition {
+    Condition::Length(length) => stats.length >= length,
+    Condition::Score(score) => stats.score >= score,
+    Condition::SurviveSeconds(seconds) => survival_time.0.elapsed_secs() >= seconds,
+    Condition::Eat(kind) => eaten.contains(&kind),
+  };
+  let mut changed = false;
+  for achievement in ACHIEVEMENTS.iter() {
+    if achievements.unlocked.contains(achievement.id) || !met(&achievement.condition) { continue; }
+    achievements.unlocked.insert(achievement.id.to_string());
+    unlocked_writer.send(AchievementUnlocked { id: achievement.id });
+    changed = true;
+  }
+  if changed {
+    if let Err(error) = save_achievements(&achievements_path(), &achievements) {
+      warn!("Couldn't save achievements: {}", error);
+    }
+  }
+}
@@ -0,0 +1,492 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::game::*;
+use crate::level::LevelData;
+
+pub struct AiSnake;
+
+// Whether the computer-controlled opponent is spawned at all.
+#[derive(Default)]
+pub struct AiOpponent(pub bool);
+
+// How many moves ahead the AI checks that it won't box itself in. At 0 it just
+// chases the food.
+pub struct AiLookahead(pub u32);
+
+impl Default for AiLookahead {
+  fn default() -> Self {
+    Self(2)
+  }
+}
+
+#[derive(Default)]
+pub struct AiSnakeState {
+  pub segments: Vec<Entity>,
+  pub last_tail_position: Option<Position>,
+}
+
+const DIRECTIONS: [Direction; 4] =
+  [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+// Bouncing off an edge is treated the same as dying on it.
+fn step(
+  position: Position,
+  direction: Direction,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+) -> Option<Position> {
+  let next = position.neighbor(direction);
+  match edges.crossed(next, arena) {
+    None => Some(next),
+    Some(WallBehavior::Wrap) => Some(next.wrapped(arena)),
+    Some(_) => None,
+  }
+}
+
+// How far apart two tiles are ignoring anything in between. Going around an
+// axis counts if either of its edges wraps, which never overestimates.
+fn distance(a: Position, b: Position, arena: &ArenaSize, edges: &EdgeConfig) -> u32 {
+  let dx = (a.x() - b.x()).abs() as u32;
+  let dy = (a.y() - b.y()).abs() as u32;
+  let wraps = |first: WallBehavior, second: WallBehavior| {
+    first == WallBehavior::Wrap || second == WallBehavior::Wrap
+  };
+  let dx = if wraps(edges.left, edges.right) { dx.min(arena.width - dx) } else { dx };
+  let dy = if wraps(edges.top, edges.bottom) { dy.min(arena.height - dy) } else { dy };
+  dx + dy
+}
+
+// A* search from `start` to the closest of `goals`, returning the direction of
+// the first step along the shortest path.
+pub fn path_direction(
+  start: Position,
+  goals: &HashSet<Position>,
+  blocked: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+) -> Option<Direction> {
+  let estimate = |position: Position| {
+    goals.iter().map(|&goal| distance(position, goal, arena, edges)).min().unwrap_or(0)
+  };
+  if goals.is_empty() { return None; }
+  // The heap only holds indices into `nodes`, as positions can't be ordered.
+  let mut nodes = vec![(start, None)];
+  let mut costs = HashMap::new();
+  let mut open = BinaryHeap::new();
+  costs.insert(start, 0);
+  open.push(Reverse((estimate(start), 0, 0)));
+  while let Some(Reverse((_, cost, index))) = open.pop() {
+    let (position, first_direction): (Position, Option<Direction>) = nodes[index];
+    if goals.contains(&position) { return first_direction; }
+    if costs.get(&position).map_or(false, |&best| best < cost) { continue; }
+    for &direction in DIRECTIONS.iter() {
+      let next = match step(position, direction, arena, edges) {
+        Some(next) => next,
+        None => continue,
+      };
+      let cost = cost + 1;
+      if blocked.contains(&next) || costs.get(&next).map_or(false, |&best| best <= cost) {
+        continue;
+      }
+      costs.insert(next, cost);
+      nodes.push((next, first_direction.or(Some(direction))));
+      open.push(Reverse((cost + estimate(next), cost, nodes.len() - 1)));
+    }
+  }
+  None
+}
+
+// How many tiles can be reached from `start` without crossing `blocked`,
+// counting `start` itself.
+pub fn open_space(
+  start: Position,
+  blocked: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+) -> usize {
+  let mut visited = HashSet::new();
+  let mut queue = VecDeque::new();
+  visited.insert(start);
+  queue.push_back(start);
+  while let Some(position) = queue.pop_front() {
+    for &direction in DIRECTIONS.iter() {
+      match step(position, direction, arena, edges) {
+        Some(next) if !blocked.contains(&next) && visited.insert(next) => queue.push_back(next),
+        _ => {}
+      }
+    }
+  }
+  visited.len()
+}
+
+fn moved(body: &VecDeque<Position>, next: Position) -> VecDeque<Position> {
+  let mut body = body.clone();
+  body.push_front(next);
+  body.pop_back();
+  body
+}
+
+// The most open space the snake can still have after `depth` more moves, its
+// tail moving out of the way as it goes. `body` is head first.
+fn lookahead_space(
+  body: &VecDeque<Position>,
+  obstacles: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+  depth: u32,
+) -> usize {
+  let blocked = obstacles.iter().chain(body.iter()).copied().collect::<HashSet<Position>>();
+  if depth <= 1 {
+    return open_space(body[0], &blocked, arena, edges);
+  }
+  DIRECTIONS
+    .iter()
+    .filter_map(|&direction| step(body[0], direction, arena, edges))
+    .filter(|next| !blocked.contains(next))
+    .map(|next| lookahead_space(&moved(body, next), obstacles, arena, edges, depth - 1))
+    .max()
+    .unwrap_or(0)
+}
+
+// Picks the AI's next move. With no lookahead it heads straight for food.
+// Otherwise it only takes the path to food if that leaves it at least as much
+// room as it is long, and heads for the most open space when it doesn't.
+// `obstacles` is everything in the way except the snake's own `body`.
+pub fn choose_direction(
+  body: &[Position],
+  current: Direction,
+  obstacles: &HashSet<Position>,
+  goals: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+  lookahead: u32,
+) -> Option<Direction> {
+  let head = *body.first()?;
+  let blocked = obstacles.iter().chain(body.iter()).copied().collect::<HashSet<Position>>();
+  let towards_food = path_direction(head, goals, &blocked, arena, edges);
+  if lookahead == 0 {
+    return towards_food.or_else(|| safe_direction(head, current, &blocked, arena, edges));
+  }
+  let body = body.iter().copied().collect::<VecDeque<Position>>();
+  let spaces = DIRECTIONS
+    .iter()
+    .copied()
+    .filter(|&direction| direction != current.opposite())
+    .filter_map(|direction| {
+      let next = step(head, direction, arena, edges).filter(|next| !blocked.contains(next))?;
+      Some((direction, lookahead_space(&moved(&body, next), obstacles, arena, edges, lookahead)))
+    })
+    .collect::<Vec<(Direction, usize)>>();
+  let roomy_path = towards_food.filter(|&direction| {
+    spaces.iter().any(|&(candidate, space)| candidate == direction && space >= body.len())
+  });
+  let roomiest = || spaces.iter().max_by_key(|(_, space)| *space).map(|&(direction, _)| direction);
+  roomy_path.or_else(roomiest)
+}
+
+// Whether the head of `body` can move in `direction` and still have at least
+// as much room as the snake is long once it's there.
+pub fn is_safe_move(
+  body: &[Position],
+  direction: Direction,
+  obstacles: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+) -> bool {
+  let head = match body.first() {
+    Some(&head) => head,
+    None => return false,
+  };
+  let blocked = obstacles.iter().chain(body.iter()).copied().collect::<HashSet<Position>>();
+  let next = match step(head, direction, arena, edges) {
+    Some(next) if !blocked.contains(&next) => next,
+    _ => return false,
+  };
+  let body = body.iter().copied().collect::<VecDeque<Position>>();
+  lookahead_space(&moved(&body, next), obstacles, arena, edges, 1) >= body.len()
+}
+
+pub fn safe_direction(
+  start: Position,
+  current: Direction,
+  blocked: &HashSet<Position>,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+) -> Option<Direction> {
+  DIRECTIONS.iter().copied().find(|&direction| {
+    direction != current.opposite() &&
+      step(start, direction, arena, edges).map_or(false, |next| !blocked.contains(&next))
+  })
+}
+
+// Head first, heading down with the tail above it. Near the top right corner
+// if that's clear, otherwise the first spot going row by row down from the top
+// where the head, the tail and the tile the AI moves into first are all on the
+// board and not `blocked`. `None` if there's no such spot.
+pub fn ai_spawn_positions(arena: &ArenaSize, blocked: &HashSet<Position>) -> Option<[Position; 2]> {
+  let preferred = Position::new(arena.width as i32 - 4, arena.height as i32 - 4);
+  let (width, height) = (arena.width as i32, arena.height as i32);
+  let others = (0..height)
+    .rev()
+    .flat_map(move |y| (0..width).rev().map(move |x| Position::new(x, y)));
+  std::iter::once(preferred)
+    .chain(others)
+    .map(|head| [head, head.neighbor(Direction::Up)])
+    .find(|&[head, tail]| {
+      [head, tail, head.neighbor(Direction::Down)]
+        .iter()
+        .all(|tile| tile.in_arena(arena) && !blocked.contains(tile))
+    })
+}
+
+fn spawn_ai_segments(
+  commands: &mut Commands,
+  materials: &Materials,
+  [start, tail]: [Position; 2],
+  visual: &VisualConfig,
+) -> Vec<Entity> {
+  let head = commands
+    .spawn_bundle(SpriteBundle {
+      material: materials.ai_head_material.clone(),
+      ..Default::default()
+    })
+    .insert(SnakeHead::new(Direction::Down))
+    .insert(SnakeSegment)
+    .insert(AiSnake)
+    .insert(start)
+    .insert(PrevPosition::new(start))
+    .insert(Size::square(visual.head_scale))
+    .id();
+  let tail = spawn_segment(commands, materials.ai_segment_material.clone(), tail, visual);
+  commands.entity(tail).insert(AiSnake);
+  vec![head, tail]
+}
+
+// Keeps clear of the level's walls, obstacles and portals, and of the players'
+// snakes and the tiles right in front of them.
+pub fn spawn_ai_snake(
+  mut commands: Commands,
+  enabled: Res<AiOpponent>,
+  materials: Res<Materials>,
+  visual: Res<VisualConfig>,
+  arena: Res<ArenaSize>,
+  level: Res<LevelData>,
+  edges: Res<EdgeConfig>,
+  spawn: Res<SnakeSpawnConfig>,
+  play_mode: Res<PlayMode>,
+  mut ai: ResMut<AiSnakeState>,
+) {
+  *ai = AiSnakeState::default();
+  if !enabled.0 { return; }
+  let mut blocked = level.walls.iter().copied().collect::<HashSet<Position>>();
+  blocked.extend(level.obstacles.iter().map(|&(position, _)| position));
+  blocked.extend(level.portals.iter().map(|&(_, position)| position));
+  for (_, spawn) in player_spawns(&level, &arena, &edges, &spawn, play_mode.two_players()) {
+    blocked.extend(spawn.positions());
+    blocked.insert(spawn.start + spawn.direction);
+  }
+  match ai_spawn_positions(&arena, &blocked) {
+    Some(tiles) => ai.segments = spawn_ai_segments(&mut commands, &materials, tiles, &visual),
+    None => warn!("Couldn't find room for the AI snake, leaving it out"),
+  }
+}
+
+// Takes the AI off the board for the rest of the run.
+fn remove_ai(commands: &mut Commands, ai: &mut AiSnakeState) {
+  for &entity in ai.segments.iter() {
+    commands.entity(entity).despawn();
+  }
+  *ai = AiSnakeState::default();
+}
+
+pub fn ai_steering(
+  timer: Res<MovementTimer>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  lookahead: Res<AiLookahead>,
+  ai: Res<AiSnakeState>,
+  mut heads: Query<&mut SnakeHead, With<AiSnake>>,
+  ai_segments: Query<&Position, With<AiSnake>>,
+  other_segments: Query<&Position, (With<SnakeSegment>, Without<AiSnake>)>,
+  walls: Query<&Position, With<Wall>>,
+  poison: Query<&Position, With<PoisonFood>>,
+  food: Query<&Position, With<Food>>,
+) {
+  if !timer.0.just_finished() { return; }
+  let mut head = match ai.segments.first().and_then(|&entity| heads.get_mut(entity).ok()) {
+    Some(head) => head,
+    None => return,
+  };
+  let body = ai.segments
+    .iter()
+    .filter_map(|&entity| ai_segments.get(entity).ok())
+    .copied()
+    .collect::<Vec<Position>>();
+  let obstacles = other_segments
+    .iter()
+    .chain(walls.iter())
+    .chain(poison.iter())
+    .copied()
+    .collect::<HashSet<Position>>();
+  let goals = food.iter().copied().collect::<HashSet<Position>>();
+  let direction = choose_direction(
+    &body,
+    head.movement_direction,
+    &obstacles,
+    &goals,
+    &arena,
+    &edges,
+    lookahead.0,
+  );
+  if let Some(direction) = direction {
+    head.input_direction = direction;
+  }
+}
+
+// Runs on the same tick as `snake_movement`, after the player has moved. The AI
+// running into anything, a player's snake included, is the end of it for this
+// run, while the player keeps going.
+pub fn ai_movement(
+  mut commands: Commands,
+  timer: Res<MovementTimer>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  mut ai: ResMut<AiSnakeState>,
+  mut heads: Query<&mut SnakeHead, With<AiSnake>>,
+  mut positions: Query<&mut Position, With<SnakeSegment>>,
+  walls: Query<&Position, (With<Wall>, Without<SnakeSegment>)>,
+) {
+  if !timer.0.just_finished() { return; }
+  let head_entity = match ai.segments.first() {
+    Some(&entity) => entity,
+    None => return,
+  };
+  let mut head = match heads.get_mut(head_entity) {
+    Ok(head) => head,
+    Err(_) => return,
+  };
+  let ai_positions = ai.segments
+    .iter()
+    .map(|&entity| positions.get_mut(entity).ok().map(|position| *position))
+    .collect::<Option<Vec<Position>>>();
+  let ai_positions = match ai_positions {
+    Some(ai_positions) => ai_positions,
+    None => return,
+  };
+  let blocked = positions
+    .iter_mut()
+    .map(|position| *position)
+    .chain(walls.iter().copied())
+    .collect::<HashSet<Position>>();
+
+  head.movement_direction = head.input_direction;
+  let next = step(ai_positions[0], head.input_direction, &arena, &edges);
+  let next = match next {
+    Some(next) if !blocked.contains(&next) => next,
+    _ => {
+      remove_ai(&mut commands, &mut ai);
+      return;
+    }
+  };
+
+  let moved = std::iter::once(next).chain(ai_positions.iter().copied());
+  for (position, &segment) in moved.zip(ai.segments.iter()) {
+    if let Ok(mut segment_position) = positions.get_mut(segment) {
+      *segment_position = position;
+    }
+  }
+  ai.last_tail_position = ai_positions.last().copied();
+}
+
+// Poison takes the AI's tail off like it does a player's, and is the end of
+// the AI if it's already as short as it can get.
+pub fn ai_eating(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  visual: Res<VisualConfig>,
+  min_length: Res<MinLength>,
+  mut ai: ResMut<AiSnakeState>,
+  heads: Query<&Position, (With<SnakeHead>, With<AiSnake>)>,
+  food: Query<(Entity, &Position), With<Food>>,
+  poison: Query<(Entity, &Position), With<PoisonFood>>,
+) {
+  // Going by `AiSnakeState` rather than the query, so an AI taken off the board
+  // this tick doesn't eat on its way out.
+  let head_position = match ai.segments.first().and_then(|&entity| heads.get(entity).ok()) {
+    Some(position) => *position,
+    None => return,
+  };
+  for (food_entity, food_position) in food.iter() {
+    if *food_position != head_position { continue; }
+    commands.entity(food_entity).despawn();
+    if let Some(tail_position) = ai.last_tail_position {
+      let material = materials.ai_segment_material.clone();
+      let segment = spawn_segment(&mut commands, material, tail_position, &visual);
+      commands.entity(segment).insert(AiSnake);
+      ai.segments.push(segment);
+    }
+  }
+  for (poison_entity, poison_position) in poison.iter() {
+    if *poison_position != head_position { continue; }
+    commands.entity(poison_entity).despawn();
+    if ai.segments.len() <= min_length.0 {
+      remove_ai(&mut commands, &mut ai);
+      return;
+    }
+    if let Some(tail) = ai.segments.pop() {
+      commands.entity(tail).despawn();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ai_spawn_moves_off_a_blocked_corner() {
+    let arena = ArenaSize { width: 10, height: 10 };
+    let preferred = ai_spawn_positions(&arena, &HashSet::new()).unwrap();
+    assert_eq!(preferred, [Position::new(6, 6), Position::new(6, 7)]);
+    let blocked = [Position::new(6, 5)].iter().copied().collect();
+    let [head, tail] = ai_spawn_positions(&arena, &blocked).unwrap();
+    for tile in [head, tail, head.neighbor(Direction::Down)].iter() {
+      assert!(tile.in_arena(&arena) && !blocked.contains(tile));
+    }
+  }
+
+  #[test]
+  fn no_ai_spawn_on_a_full_board() {
+    let arena = ArenaSize { width: 3, height: 3 };
+    let blocked = (0..3).map(|x| Position::new(x, 1)).collect();
+    assert_eq!(ai_spawn_positions(&arena, &blocked), None);
+  }
+
+  #[test]
+  fn a_crashed_ai_is_gone_for_good() {
+    let mut world = World::default();
+    let mut timer = MovementTimer::default();
+    let interval = timer.0.duration();
+    timer.0.tick(interval);
+    world.insert_resource(timer);
+    world.insert_resource(ArenaSize { width: 5, height: 5 });
+    world.insert_resource(EdgeConfig::default());
+    let head = world
+      .spawn()
+      .insert(Position::new(0, 2))
+      .insert(SnakeSegment)
+      .insert(AiSnake)
+      .insert(SnakeHead::new(Direction::Left))
+      .id();
+    let tail = world.spawn().insert(Position::new(1, 2)).insert(SnakeSegment).insert(AiSnake).id();
+    world.insert_resource(AiSnakeState { segments: vec![head, tail], last_tail_position: None });
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(ai_movement.system());
+    stage.run(&mut world);
+    assert!(world.get_resource::<AiSnakeState>().unwrap().segments.is_empty());
+    assert!(world.get_entity(head).is_none() && world.get_entity(tail).is_none());
+  }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Settings given on the command line, e.g. `--width 20 --height 15 --seed 42`.
+// Anything left out keeps its default.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GameArgs {
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub seed: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ArgError {
+  UnknownArgument(String),
+  MissingValue(String),
+  InvalidValue { argument: String, value: String },
+}
+
+impl fmt::Display for ArgError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::UnknownArgument(argument) => write!(f, "unknown argument '{}'", argument),
+      Self::MissingValue(argument) => write!(f, "'{}' needs a value", argument),
+      Self::InvalidValue { argument, value } => {
+        write!(f, "invalid value '{}' for '{}'", value, argument)
+      }
+    }
+  }
+}
+
+fn parse_value<T: FromStr>(argument: &str, value: Option<String>) -> Result<T, ArgError> {
+  let value = value.ok_or_else(|| ArgError::MissingValue(argument.to_string()))?;
+  value.parse().map_err(|_| ArgError::InvalidValue { argument: argument.to_string(), value })
+}
+
+// Board sizes have to be at least one tile.
+fn parse_size(argument: &str, value: Option<String>) -> Result<u32, ArgError> {
+  let text = value.clone().unwrap_or_default();
+  match parse_value(argument, value)? {
+    0 => Err(ArgError::InvalidValue { argument: argument.to_string(), value: text }),
+    size => Ok(size),
+  }
+}
+
+// Bad arguments are reported and skipped rather than stopping the game.
+pub fn parse_args_from<I: IntoIterator<Item = String>>(args: I) -> (GameArgs, Vec<ArgError>) {
+  let mut parsed = GameArgs::default();
+  let mut errors = Vec::new();
+  let mut args = args.into_iter();
+  while let Some(argument) = args.next() {
+    let result = match argument.as_str() {
+      "--width" => parse_size(&argument, args.next()).map(|width| parsed.width = Some(width)),
+      "--height" => parse_size(&argument, args.next()).map(|height| parsed.height = Some(height)),
+      "--seed" => parse_value(&argument, args.next()).map(|seed| parsed.seed = Some(seed)),
+      _ => Err(ArgError::UnknownArgument(argument.clone())),
+    };
+    if let Err(error) = result {
+      errors.push(error);
+    }
+  }
+  (parsed, errors)
+}
+
+// Parses the process's own arguments, printing anything wrong with them.
+pub fn parse_args() -> GameArgs {
+  let (args, errors) = parse_args_from(std::env::args().skip(1));
+  for error in errors {
+    eprintln!("{}, using the default", error);
+  }
+  args
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> (GameArgs, Vec<ArgError>) {
+    parse_args_from(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn valid_arguments_are_all_parsed() {
+    let (args, errors) = parse(&["--width", "20", "--height", "15", "--seed", "42"]);
+    assert_eq!(args, GameArgs { width: Some(20), height: Some(15), seed: Some(42) });
+    assert!(errors.is_empty());
+  }
+
+  #[test]
+  fn an_unknown_flag_is_skipped() {
+    let (args, errors) = parse(&["--fast", "--width", "20"]);
+    assert_eq!(args.width, Some(20));
+    assert!(matches!(errors.as_slice(), [ArgError::UnknownArgument(flag)] if flag == "--fast"));
+  }
+
+  #[test]
+  fn a_flag_without_a_value_is_reported() {
+    let (args, errors) = parse(&["--seed"]);
+    assert_eq!(args, GameArgs::default());
+    assert!(matches!(errors.as_slice(), [ArgError::MissingValue(flag)] if flag == "--seed"));
+  }
+
+  #[test]
+  fn a_non_numeric_value_is_reported() {
+    let (args, errors) = parse(&["--height", "tall"]);
+    assert_eq!(args.height, None);
+    assert!(matches!(errors.as_slice(), [ArgError::InvalidValue { value, .. }] if value == "tall"));
+  }
+
+  #[test]
+  fn a_zero_size_is_rejected_however_it_is_written() {
+    for zero in ["0", "00", "+0"].iter() {
+      let (args, errors) = parse(&["--width", zero]);
+      assert_eq!(args.width, None);
+      assert!(matches!(errors.as_slice(), [ArgError::InvalidValue { value, .. }] if value == zero));
+    }
+  }
+}
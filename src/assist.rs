@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::ai::is_safe_move;
+use crate::game::*;
+
+const DIRECTIONS: [Direction; 4] =
+  [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+// Whether the tiles next to player one's head that lead into a dead end are
+// shaded. It's only a hint and never steers the snake.
+#[derive(Default)]
+pub struct Assist(pub bool);
+
+// The directions player one's head can't safely take, worked out once a tick.
+#[derive(Default)]
+pub struct UnsafeMoves(pub Vec<Direction>);
+
+pub struct AssistOverlay(pub Direction);
+
+pub fn spawn_assist_overlays(mut commands: Commands, materials: Res<Materials>) {
+  for &direction in DIRECTIONS.iter() {
+    commands
+      .spawn_bundle(SpriteBundle {
+        material: materials.assist_material.clone(),
+        visible: Visible { is_visible: false, is_transparent: true },
+        ..Default::default()
+      })
+      .insert(AssistOverlay(direction));
+  }
+}
+
+// Runs after the snakes have moved and grown. Reversing is never a legal move,
+// so it isn't flagged.
+pub fn unsafe_moves(
+  assist: Res<Assist>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  segments: Res<SnakeSegments>,
+  mut unsafe_moves: ResMut<UnsafeMoves>,
+  heads: Query<(&SnakeHead, &Player)>,
+  positions: Query<&Position, With<SnakeSegment>>,
+  walls: Query<&Position, (With<Wall>, Without<SnakeSegment>)>,
+) {
+  unsafe_moves.0.clear();
+  if !assist.0 { return; }
+  let head = match heads.iter().find(|(_, player)| **player == Player::One) {
+    Some((head, _)) => head,
+    None => return,
+  };
+  let player_segments = segments.get(Player::One).unwrap_or_default();
+  let body = player_segments
+    .iter()
+    .filter_map(|&entity| positions.get(entity).ok())
+    .copied()
+    .collect::<Vec<Position>>();
+  let mut obstacles = positions.iter().chain(walls.iter()).copied().collect::<HashSet<Position>>();
+  for position in body.iter() {
+    obstacles.remove(position);
+  }
+  unsafe_moves.0 = DIRECTIONS
+    .iter()
+    .copied()
+    .filter(|&direction| direction != head.movement_direction.opposite())
+    .filter(|&direction| !is_safe_move(&body, direction, &obstacles, &arena, &edges))
+    .collect();
+}
+
+// Runs after the head has been placed for the frame, so the overlays follow it
+// between ticks.
+pub fn assist_overlays(
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  unsafe_moves: Res<UnsafeMoves>,
+  heads: Query<(&Transform, &Player), (With<SnakeHead>, Without<AssistOverlay>)>,
+  mut overlays: Query<(&AssistOverlay, &mut Transform, &mut Sprite, &mut Visible)>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let head = heads.iter().find(|(_, player)| **player == Player::One);
+  let board = board_size(window, &arena, *scaling);
+  let tile = Vec2::new(board.x / arena.width as f32, board.y / arena.height as f32);
+  for (overlay, mut transform, mut sprite, mut visible) in overlays.iter_mut() {
+    let head = match head {
+      Some((head, _)) if unsafe_moves.0.contains(&overlay.0) => head.translation,
+      _ => {
+        visible.is_visible = false;
+        continue;
+      }
+    };
+    let (x, y) = overlay.0.to_offset();
+    transform.translation = Vec3::new(head.x + x as f32 * tile.x, head.y + y as f32 * tile.y, 0.5);
+    sprite.size = tile;
+    visible.is_visible = true;
+  }
+}
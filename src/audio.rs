@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+use crate::game::{GameOverEvent, ScoreEvent};
+
+// Length of `sounds/music.wav`, the music is restarted every time it runs out.
+// Only used until the file has loaded, after which `music_length` times the
+// loop by the file itself.
+pub const MUSIC_LENGTH: f32 = 4.0;
+
+pub struct AudioHandles {
+  pub eat: Handle<AudioSource>,
+  pub death: Handle<AudioSource>,
+  pub music: Handle<AudioSource>,
+}
+
+#[derive(Default)]
+pub struct Muted(pub bool);
+
+pub struct MusicTimer(pub Timer);
+
+impl Default for MusicTimer {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(MUSIC_LENGTH, false);
+    // Finishes on the first tick so the music kicks in as soon as it's loaded.
+    timer.set_elapsed(timer.duration());
+    Self(timer)
+  }
+}
+
+pub fn load_audio(mut commands: Commands, asset_server: Res<AssetServer>) {
+  commands.insert_resource(AudioHandles {
+    eat: asset_server.load("sounds/eat.wav"),
+    death: asset_server.load("sounds/death.wav"),
+    music: asset_server.load("sounds/music.wav"),
+  });
+}
+
+// All playback goes through here. Sounds that haven't finished loading yet are
+// skipped rather than queued up, and nothing new starts while muted.
+fn play(
+  audio: &Audio,
+  asset_server: &AssetServer,
+  muted: &Muted,
+  handle: &Handle<AudioSource>,
+) -> bool {
+  if muted.0 || asset_server.get_load_state(handle) != LoadState::Loaded { return false; }
+  audio.play(handle.clone());
+  true
+}
+
+// Bevy can't stop a single sound, so muting swaps in a new audio output, which
+// cuts off everything still playing through the old one, the music included.
+// Unmuting starts the music over straight away.
+pub fn toggle_mute(world: &mut World) {
+  let pressed = world
+    .get_resource::<Input<KeyCode>>()
+    .map_or(false, |input| input.just_pressed(KeyCode::M));
+  if !pressed { return; }
+  let muted = {
+    let mut muted = world.get_resource_mut::<Muted>().unwrap();
+    muted.0 = !muted.0;
+    muted.0
+  };
+  if muted {
+    if world.get_non_send_resource::<AudioOutput>().is_some() {
+      world.insert_non_send(AudioOutput::<AudioSource>::default());
+    }
+  } else if let Some(mut timer) = world.get_resource_mut::<MusicTimer>() {
+    let length = timer.0.duration();
+    timer.0.set_elapsed(length);
+  }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+  let word = bytes.get(at..at + 4)?;
+  Some(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+// How long a PCM `.wav` plays for, worked out from its header. `None` if the
+// bytes aren't one.
+pub fn wav_duration(bytes: &[u8]) -> Option<Duration> {
+  if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WAVE" { return None; }
+  let mut byte_rate = None;
+  let mut offset = 12;
+  while let Some(id) = bytes.get(offset..offset + 4) {
+    let size = read_u32(bytes, offset + 4)?;
+    if id == b"fmt " {
+      byte_rate = read_u32(bytes, offset + 16);
+    } else if id == b"data" {
+      let byte_rate = byte_rate.filter(|&byte_rate| byte_rate > 0)?;
+      return Some(Duration::from_secs_f64(size as f64 / byte_rate as f64));
+    }
+    // Chunks are padded out to an even length.
+    offset += 8 + size as usize + size as usize % 2;
+  }
+  None
+}
+
+// Once the music has loaded, the loop is timed by the file rather than by
+// `MUSIC_LENGTH`, so swapping in a longer or shorter track just works. Runs
+// before `background_music`, which can't have started it yet.
+pub fn music_length(
+  handles: Res<AudioHandles>,
+  sources: Res<Assets<AudioSource>>,
+  mut timer: ResMut<MusicTimer>,
+  mut measured: Local<bool>,
+) {
+  if *measured { return; }
+  let source = match sources.get(&handles.music) {
+    Some(source) => source,
+    None => return,
+  };
+  *measured = true;
+  if let Some(length) = wav_duration(&source.bytes) {
+    timer.0.set_duration(length);
+    timer.0.set_elapsed(length);
+  }
+}
+
+pub fn background_music(
+  time: Res<Time>,
+  audio: Res<Audio>,
+  asset_server: Res<AssetServer>,
+  muted: Res<Muted>,
+  handles: Res<AudioHandles>,
+  mut timer: ResMut<MusicTimer>,
+) {
+  if timer.0.tick(time.delta()).finished() && play(&audio, &asset_server, &muted, &handles.music) {
+    timer.0.reset();
+  }
+}
+
+pub fn eat_sound(
+  audio: Res<Audio>,
+  asset_server: Res<AssetServer>,
+  muted: Res<Muted>,
+  handles: Res<AudioHandles>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  if score_reader.iter().next().is_some() {
+    play(&audio, &asset_server, &muted, &handles.eat);
+  }
+}
+
+pub fn death_sound(
+  audio: Res<Audio>,
+  asset_server: Res<AssetServer>,
+  muted: Res<Muted>,
+  handles: Res<AudioHandles>,
+  mut game_over_reader: EventReader<GameOverEvent>,
+) {
+  if game_over_reader.iter().next().is_some() {
+    play(&audio, &asset_server, &muted, &handles.death);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn music_length_comes_from_the_file() {
+    let length = wav_duration(include_bytes!("../assets/sounds/music.wav")).unwrap();
+    assert!((length.as_secs_f32() - MUSIC_LENGTH).abs() < 0.01);
+  }
+
+  #[test]
+  fn anything_but_a_wav_has_no_length() {
+    assert_eq!(wav_duration(b""), None);
+    assert_eq!(wav_duration(b"RIFF\0\0\0\0WAVE"), None);
+    assert_eq!(wav_duration(b"ID3 this is an mp3"), None);
+  }
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use crate::effects::ScreenShake;
+use crate::game::*;
+
+// How quickly the camera catches up with the head, higher is snappier.
+pub const CAMERA_SMOOTHING: f32 = 5.0;
+pub const ZOOM_STEP: f32 = 1.25;
+pub const MIN_ZOOM: f32 = 0.1;
+
+// Whether the camera follows player one's head instead of showing the whole
+// board, for boards too big to read at once.
+#[derive(Default)]
+pub struct CameraFollow(pub bool);
+
+// The camera's scale while following. At 0.5 it shows half as much of the
+// board across as fits in the window, at 1.0 all of it.
+pub struct CameraZoom(pub f32);
+
+impl Default for CameraZoom {
+  fn default() -> Self {
+    Self(0.5)
+  }
+}
+
+// Where the camera is looking, before any screen shake.
+#[derive(Default)]
+pub struct CameraFocus(pub Vec2);
+
+pub fn camera_zoom(
+  input: Res<Input<KeyCode>>,
+  follow: Res<CameraFollow>,
+  mut zoom: ResMut<CameraZoom>,
+) {
+  if !follow.0 { return; }
+  if input.just_pressed(KeyCode::Equals) || input.just_pressed(KeyCode::NumpadAdd) {
+    zoom.0 = (zoom.0 / ZOOM_STEP).max(MIN_ZOOM);
+  }
+  if input.just_pressed(KeyCode::Minus) || input.just_pressed(KeyCode::NumpadSubtract) {
+    zoom.0 = (zoom.0 * ZOOM_STEP).min(1.0);
+  }
+}
+
+// Runs once everything has been laid out for the frame, so it follows the head
+// to where it's drawn. The view is kept from scrolling past the board's edges.
+pub fn camera_follow(
+  time: Res<Time>,
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  follow: Res<CameraFollow>,
+  zoom: Res<CameraZoom>,
+  shake: Res<ScreenShake>,
+  mut focus: ResMut<CameraFocus>,
+  heads: Query<(&Transform, &Player), (With<SnakeHead>, Without<MainCamera>)>,
+  mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let scale = if follow.0 { zoom.0 } else { 1.0 };
+  if follow.0 {
+    let head = heads.iter().find(|(_, player)| **player == Player::One);
+    let target = head.map_or(focus.0, |(transform, _)| transform.translation.truncate());
+    let smoothing = 1.0 - (-CAMERA_SMOOTHING * time.delta_seconds()).exp();
+    let board = board_size(window, &arena, *scaling);
+    let view = Vec2::new(window.width(), window.height()) * scale;
+    let limit = ((board - view) / 2.0).max(Vec2::ZERO);
+    focus.0 = focus.0.lerp(target, smoothing).max(-limit).min(limit);
+  } else {
+    focus.0 = Vec2::ZERO;
+  }
+  for mut transform in cameras.iter_mut() {
+    transform.translation.x = focus.0.x + shake.offset.x;
+    transform.translation.y = focus.0.y + shake.offset.y;
+    transform.scale = Vec3::new(scale, scale, 1.0);
+  }
+}
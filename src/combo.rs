@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::game::{GameOverEvent, ScoreEvent, SIMULATION_STEP};
+
+// Eating again within `window` seconds of the last food bumps the multiplier,
+// up to `max_multiplier`.
+pub struct ComboConfig {
+  pub window: f32,
+  pub max_multiplier: u32,
+}
+
+impl Default for ComboConfig {
+  fn default() -> Self {
+    Self {
+      window: 3.0,
+      max_multiplier: 5,
+    }
+  }
+}
+
+// `timer` is running while a combo can still be continued.
+pub struct Combo {
+  pub multiplier: u32,
+  pub timer: Option<Timer>,
+}
+
+impl Default for Combo {
+  fn default() -> Self {
+    Self { multiplier: 1, timer: None }
+  }
+}
+
+// Only keeps the multiplier up to date. `scoring` applies it to the points.
+pub fn combo_scoring(
+  config: Res<ComboConfig>,
+  mut combo: ResMut<Combo>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  let step = Duration::from_secs_f64(SIMULATION_STEP);
+  let lapsed = combo.timer.as_mut().map_or(false, |timer| timer.tick(step).finished());
+  if lapsed {
+    *combo = Combo::default();
+  }
+  for event in score_reader.iter() {
+    if event.delta <= 0 { continue; }
+    if combo.timer.is_some() {
+      combo.multiplier = (combo.multiplier + 1).min(config.max_multiplier);
+    }
+    combo.timer = Some(Timer::from_seconds(config.window, false));
+  }
+}
+
+pub fn reset_combo(mut combo: ResMut<Combo>, mut game_over_reader: EventReader<GameOverEvent>) {
+  if game_over_reader.iter().next().is_some() {
+    *combo = Combo::default();
+  }
+}
@@ -0,0 +1,85 @@
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use crate::game::{Food, Player, Position, SnakeHead, SnakeSegments};
+
+pub const DEBUG_OVERLAY_KEY: KeyCode = KeyCode::F3;
+
+// Whether the debug overlay is shown. The text only exists while it is, so a
+// hidden overlay costs nothing.
+#[derive(Default)]
+pub struct DebugOverlay(pub bool);
+
+pub struct DebugText;
+
+pub fn toggle_debug_overlay(input: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+  if input.just_pressed(DEBUG_OVERLAY_KEY) {
+    overlay.0 = !overlay.0;
+  }
+}
+
+pub fn debug_overlay_visibility(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  overlay: Res<DebugOverlay>,
+  texts: Query<Entity, With<DebugText>>,
+) {
+  if !overlay.is_changed() { return; }
+  for entity in texts.iter() {
+    commands.entity(entity).despawn();
+  }
+  if !overlay.0 { return; }
+  commands
+    .spawn_bundle(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          bottom: Val::Px(5.0),
+          right: Val::Px(10.0),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      text: Text::with_section(
+        "",
+        TextStyle {
+          font: asset_server.load("fonts/DejaVuSans.ttf"),
+          font_size: 16.0,
+          color: Color::YELLOW,
+        },
+        Default::default(),
+      ),
+      ..Default::default()
+    })
+    .insert(DebugText);
+}
+
+pub fn debug_overlay_text(
+  overlay: Res<DebugOverlay>,
+  diagnostics: Res<Diagnostics>,
+  segments: Res<SnakeSegments>,
+  heads: Query<(&Position, &Player), With<SnakeHead>>,
+  food: Query<Entity, With<Food>>,
+  mut texts: Query<&mut Text, With<DebugText>>,
+) {
+  if !overlay.0 { return; }
+  let fps = diagnostics
+    .get(FrameTimeDiagnosticsPlugin::FPS)
+    .and_then(|fps| fps.average())
+    .unwrap_or(0.0);
+  let length = segments.len(Player::One);
+  let head = match heads.iter().find(|(_, player)| **player == Player::One) {
+    Some((position, _)) => format!("({}, {})", position.x(), position.y()),
+    None => "-".to_string(),
+  };
+  let value = format!(
+    "FPS: {:.0}  Length: {}  Head: {}  Food: {}",
+    fps,
+    length,
+    head,
+    food.iter().count(),
+  );
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = value.clone();
+  }
+}
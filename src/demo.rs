@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::ai::{choose_direction, AiLookahead};
+use crate::game::*;
+
+// Marks everything the attract mode puts on the board. None of it is a
+// `SnakeSegment` or `Food`, so the game's own systems leave it alone.
+pub struct DemoEntity;
+
+pub struct DemoTimer(pub Timer);
+
+impl Default for DemoTimer {
+  fn default() -> Self {
+    Self(Timer::from_seconds(MOVEMENT_INTERVAL, true))
+  }
+}
+
+// The snake the AI plays on the title screen, head first, and its food.
+#[derive(Default)]
+pub struct DemoState {
+  pub segments: Vec<(Entity, Position)>,
+  pub direction: Option<Direction>,
+  pub food: Option<(Entity, Position)>,
+}
+
+fn spawn_demo_tile(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  position: Position,
+  size: f32,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(DemoEntity)
+    .insert(position)
+    .insert(Size::square(size))
+    .id()
+}
+
+// Uses the thread's RNG so watching the demo doesn't change a seeded game.
+fn spawn_demo_food(
+  commands: &mut Commands,
+  materials: &Materials,
+  arena: &ArenaSize,
+  demo: &mut DemoState,
+) {
+  let taken = demo.segments.iter().map(|&(_, position)| position).collect::<HashSet<Position>>();
+  let free = (0..arena.height as i32)
+    .flat_map(|y| (0..arena.width as i32).map(move |x| Position::new(x, y)))
+    .filter(|position| !taken.contains(position))
+    .collect::<Vec<Position>>();
+  demo.food = free.choose(&mut rand::thread_rng()).map(|&position| {
+    (spawn_demo_tile(commands, materials.food_material.clone(), position, 0.8), position)
+  });
+}
+
+fn despawn_demo_entities(commands: &mut Commands, entities: &Query<Entity, With<DemoEntity>>) {
+  for entity in entities.iter() {
+    commands.entity(entity).despawn();
+  }
+}
+
+fn start_demo(
+  commands: &mut Commands,
+  materials: &Materials,
+  arena: &ArenaSize,
+  visual: &VisualConfig,
+  demo: &mut DemoState,
+) {
+  let head = Position::new(arena.width as i32 / 2, arena.height as i32 / 2);
+  let positions = [head, head + Direction::Down, head + Direction::Down + Direction::Down];
+  *demo = DemoState::default();
+  demo.direction = Some(Direction::Up);
+  demo.segments = positions
+    .iter()
+    .enumerate()
+    .map(|(index, &position)| {
+      let (material, size) = if index == 0 {
+        (materials.ai_head_material.clone(), visual.head_scale)
+      } else {
+        (materials.ai_segment_material.clone(), visual.segment_scale)
+      };
+      (spawn_demo_tile(commands, material, position, size), position)
+    })
+    .collect();
+  spawn_demo_food(commands, materials, arena, demo);
+}
+
+pub fn spawn_demo(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  visual: Res<VisualConfig>,
+  mut demo: ResMut<DemoState>,
+) {
+  start_demo(&mut commands, &materials, &arena, &visual, &mut demo);
+}
+
+// Steers with the same search as the AI opponent and starts over whenever the
+// snake runs into itself or the edge of the board.
+pub fn demo_movement(
+  mut commands: Commands,
+  time: Res<Time>,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  visual: Res<VisualConfig>,
+  lookahead: Res<AiLookahead>,
+  mut timer: ResMut<DemoTimer>,
+  mut demo: ResMut<DemoState>,
+  entities: Query<Entity, With<DemoEntity>>,
+  mut positions: Query<&mut Position, With<DemoEntity>>,
+) {
+  if !timer.0.tick(time.delta()).just_finished() { return; }
+  let body = demo.segments.iter().map(|&(_, position)| position).collect::<Vec<Position>>();
+  let current = demo.direction.unwrap_or(Direction::Up);
+  let goals = demo.food.iter().map(|&(_, position)| position).collect::<HashSet<Position>>();
+  let edges = EdgeConfig::default();
+  let obstacles = HashSet::new();
+  let direction = choose_direction(&body, current, &obstacles, &goals, &arena, &edges, lookahead.0);
+  let next = direction.map(|direction| body[0] + direction);
+  let next = match next {
+    Some(next) if next.in_arena(&arena) && !body.contains(&next) => next,
+    _ => {
+      despawn_demo_entities(&mut commands, &entities);
+      start_demo(&mut commands, &materials, &arena, &visual, &mut demo);
+      return;
+    }
+  };
+  demo.direction = direction;
+  let tail = *demo.segments.last().unwrap();
+  for (index, position) in std::iter::once(next).chain(body.iter().copied()).enumerate() {
+    if let Some(segment) = demo.segments.get_mut(index) {
+      segment.1 = position;
+      if let Ok(mut current) = positions.get_mut(segment.0) {
+        *current = position;
+      }
+    }
+  }
+  if demo.food.map_or(false, |(_, position)| position == next) {
+    let (food, _) = demo.food.take().unwrap();
+    commands.entity(food).despawn();
+    let material = materials.ai_segment_material.clone();
+    let segment = spawn_demo_tile(&mut commands, material, tail.1, visual.segment_scale);
+    demo.segments.push((segment, tail.1));
+    spawn_demo_food(&mut commands, &materials, &arena, &mut demo);
+  }
+}
+
+// Nothing of the demo is left once a real game starts.
+pub fn despawn_demo(
+  mut commands: Commands,
+  mut demo: ResMut<DemoState>,
+  entities: Query<Entity, With<DemoEntity>>,
+) {
+  despawn_demo_entities(&mut commands, &entities);
+  *demo = DemoState::default();
+}
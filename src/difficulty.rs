@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::ai::AiLookahead;
+use crate::game::*;
+
+// One setting for how hard the game is, which sets the snake's speed, how much
+// food there is, whether poison spawns and how smart the AI is. Without one,
+// those are left as they were configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+  Easy,
+  Normal,
+  Hard,
+}
+
+pub struct DifficultyPreset {
+  pub movement_interval: Duration,
+  pub speed_factor: f32,
+  pub food_count: u32,
+  pub poison: bool,
+  // Multiplies the time the snake can go without eating.
+  pub hunger_scale: f32,
+  pub ai_lookahead: u32,
+}
+
+impl Difficulty {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Easy => "Easy",
+      Self::Normal => "Normal",
+      Self::Hard => "Hard",
+    }
+  }
+
+  // `Normal` matches the defaults of the resources it sets.
+  pub fn preset(&self) -> DifficultyPreset {
+    match self {
+      Self::Easy => DifficultyPreset {
+        movement_interval: Duration::from_secs_f32(0.2),
+        speed_factor: 0.985,
+        food_count: 2,
+        poison: false,
+        hunger_scale: 1.5,
+        ai_lookahead: 0,
+      },
+      Self::Normal => DifficultyPreset {
+        movement_interval: Duration::from_secs_f32(MOVEMENT_INTERVAL),
+        speed_factor: SpeedConfig::default().factor,
+        food_count: FoodCount::default().0,
+        poison: true,
+        hunger_scale: 1.0,
+        ai_lookahead: AiLookahead::default().0,
+      },
+      Self::Hard => DifficultyPreset {
+        movement_interval: Duration::from_secs_f32(0.1),
+        speed_factor: 0.95,
+        food_count: 1,
+        poison: true,
+        hunger_scale: 0.6,
+        ai_lookahead: 4,
+      },
+    }
+  }
+}
+
+// How long the snake can go without eating before the difficulty scales it.
+pub struct HungerLimit(pub Duration);
+
+// Picked on the title screen with the number keys.
+pub fn choose_difficulty(
+  mut commands: Commands,
+  input: Res<Input<KeyCode>>,
+  state: Res<State<GameState>>,
+) {
+  if *state.current() != GameState::Menu { return; }
+  let keys = [
+    (KeyCode::Key1, Difficulty::Easy),
+    (KeyCode::Key2, Difficulty::Normal),
+    (KeyCode::Key3, Difficulty::Hard),
+  ];
+  if let Some(&(_, difficulty)) = keys.iter().find(|(key, _)| input.just_pressed(*key)) {
+    commands.insert_resource(difficulty);
+  }
+}
+
+// A preset only turns poison on or off. When it's on, the chance is whatever
+// was configured before the first preset was applied.
+pub fn apply_difficulty(
+  mut configured_poison: Local<Option<f32>>,
+  difficulty: Option<Res<Difficulty>>,
+  mut speed_config: ResMut<SpeedConfig>,
+  mut base_interval: ResMut<BaseInterval>,
+  mut timer: ResMut<MovementTimer>,
+  mut food_count: ResMut<FoodCount>,
+  mut poison_chance: ResMut<PoisonChance>,
+  mut ai_lookahead: ResMut<AiLookahead>,
+  hunger_limit: Option<Res<HungerLimit>>,
+  hunger: Option<ResMut<Hunger>>,
+) {
+  let difficulty = match difficulty {
+    Some(difficulty) if difficulty.is_changed() => difficulty,
+    _ => return,
+  };
+  let preset = difficulty.preset();
+  speed_config.initial_interval = preset.movement_interval;
+  speed_config.factor = preset.speed_factor;
+  base_interval.0 = preset.movement_interval;
+  timer.0.set_duration(preset.movement_interval);
+  food_count.0 = preset.food_count;
+  let configured_poison = *configured_poison.get_or_insert(poison_chance.0);
+  poison_chance.0 = if preset.poison { configured_poison } else { 0.0 };
+  ai_lookahead.0 = preset.ai_lookahead;
+  if let (Some(limit), Some(mut hunger)) = (hunger_limit, hunger) {
+    hunger.0.set_duration(limit.0.mul_f32(preset.hunger_scale));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn difficulty_world(poison_chance: f32) -> World {
+    let mut world = World::default();
+    world.insert_resource(SpeedConfig::default());
+    world.insert_resource(BaseInterval::default());
+    world.insert_resource(MovementTimer::default());
+    world.insert_resource(FoodCount::default());
+    world.insert_resource(PoisonChance(poison_chance));
+    world.insert_resource(AiLookahead::default());
+    world
+  }
+
+  #[test]
+  fn presets_keep_the_configured_poison_chance() {
+    let mut world = difficulty_world(0.5);
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(apply_difficulty.system());
+    let mut poison_after = |world: &mut World, difficulty: Difficulty| {
+      world.insert_resource(difficulty);
+      stage.run(world);
+      world.get_resource::<PoisonChance>().unwrap().0
+    };
+    assert_eq!(poison_after(&mut world, Difficulty::Hard), 0.5);
+    assert_eq!(poison_after(&mut world, Difficulty::Easy), 0.0);
+    assert_eq!(poison_after(&mut world, Difficulty::Normal), 0.5);
+  }
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::game::*;
+
+pub const SHAKE_INTENSITY: f32 = 8.0;
+pub const SHAKE_DURATION: f32 = 0.3;
+pub const PARTICLE_LIFETIME: f32 = 0.4;
+pub const PARTICLE_SIZE: f32 = 6.0;
+pub const FOOD_PULSE_SPEED: f32 = 4.0;
+pub const FOOD_PULSE_AMOUNT: f32 = 0.15;
+// Degrees of hue per second.
+pub const RAINBOW_FOOD_SPEED: f32 = 90.0;
+
+// Jitters the camera by up to `intensity` pixels, fading out over the length of
+// `timer`. The timer starts out finished so nothing shakes until a death.
+// `offset` is the current jitter, which `camera_follow` adds to the camera.
+pub struct ScreenShake {
+  pub intensity: f32,
+  pub timer: Timer,
+  pub offset: Vec2,
+}
+
+// Particles are plain sprites without a `Position`, so nothing in the game
+// treats them as occupying a tile.
+pub struct Particle {
+  pub velocity: Vec2,
+  pub timer: Timer,
+}
+
+// How many particles burst out of eaten food and how fast, in pixels per second.
+pub struct ParticleConfig {
+  pub count: u32,
+  pub speed: f32,
+}
+
+impl Default for ParticleConfig {
+  fn default() -> Self {
+    Self { count: 8, speed: 120.0 }
+  }
+}
+
+// Food gently grows and shrinks so it's easier to spot. Only the sprite's scale
+// changes, never its `Position`.
+#[derive(Default)]
+pub struct AnimatedFood(pub bool);
+
+// Food cycles through the hues, starting from the theme's food color.
+#[derive(Default)]
+pub struct RainbowFood(pub bool);
+
+// Particle directions and shake offsets draw from their own stream, seeded along
+// with `GameRng`. Effects can be reproduced from a seed, but however many
+// particles are drawn, the gameplay stream and so a replay stay the same.
+pub struct CosmeticRng(pub StdRng);
+
+impl CosmeticRng {
+  pub fn from_seed(seed: u64) -> Self {
+    Self(StdRng::seed_from_u64(seed))
+  }
+}
+
+impl Default for CosmeticRng {
+  fn default() -> Self {
+    Self(StdRng::from_entropy())
+  }
+}
+
+impl Default for ScreenShake {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(SHAKE_DURATION, false);
+    timer.tick(timer.duration());
+    Self { intensity: SHAKE_INTENSITY, timer, offset: Vec2::ZERO }
+  }
+}
+
+pub fn spawn_particles(
+  mut commands: Commands,
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  config: Res<ParticleConfig>,
+  materials: Res<Materials>,
+  mut rng: ResMut<CosmeticRng>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  for event in score_reader.iter() {
+    let material = match event.kind {
+      FoodKind::Normal => &materials.food_material,
+      FoodKind::Bonus => &materials.bonus_material,
+      FoodKind::Poison => &materials.poison_material,
+      FoodKind::Big => &materials.big_food_material,
+    };
+    let mut translation = tile_translation(&event.position, board, &arena);
+    translation.z = 0.5;
+    for _ in 0..config.count {
+      let angle = rng.0.gen_range(0.0..std::f32::consts::TAU);
+      commands
+        .spawn_bundle(SpriteBundle {
+          material: material.clone(),
+          sprite: Sprite::new(Vec2::splat(PARTICLE_SIZE)),
+          transform: Transform::from_translation(translation),
+          ..Default::default()
+        })
+        .insert(Particle {
+          velocity: Vec2::new(angle.cos(), angle.sin()) * config.speed,
+          timer: Timer::from_seconds(PARTICLE_LIFETIME, false),
+        });
+    }
+  }
+}
+
+// Particles shrink away to nothing as they fly outwards.
+pub fn animate_particles(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut particles: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+  for (entity, mut particle, mut transform) in particles.iter_mut() {
+    if particle.timer.tick(time.delta()).finished() {
+      commands.entity(entity).despawn();
+      continue;
+    }
+    transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+    transform.scale = Vec3::splat(1.0 - particle.timer.percent());
+  }
+}
+
+pub fn start_screen_shake(
+  mut shake: ResMut<ScreenShake>,
+  mut game_over_reader: EventReader<GameOverEvent>,
+) {
+  if game_over_reader.iter().last().is_some() {
+    shake.timer.reset();
+  }
+}
+
+pub fn screen_shake(
+  time: Res<Time>,
+  mut shake: ResMut<ScreenShake>,
+  mut rng: ResMut<CosmeticRng>,
+) {
+  if shake.timer.finished() { return; }
+  shake.timer.tick(time.delta());
+  shake.offset = if shake.timer.finished() {
+    Vec2::ZERO
+  } else {
+    let strength = shake.intensity * (1.0 - shake.timer.percent());
+    Vec2::new(rng.0.gen_range(-1.0..=1.0), rng.0.gen_range(-1.0..=1.0)) * strength
+  };
+}
+
+// Each food gets its own phase from its entity, so they don't all pulse in step.
+pub fn animate_food(
+  time: Res<Time>,
+  animated: Res<AnimatedFood>,
+  mut food: Query<(Entity, &mut Transform), With<Food>>,
+) {
+  let elapsed = time.seconds_since_startup() as f32;
+  for (entity, mut transform) in food.iter_mut() {
+    let scale = if animated.0 {
+      let phase = (entity.id() as f32 * 0.618).fract() * std::f32::consts::TAU;
+      1.0 + FOOD_PULSE_AMOUNT * (elapsed * FOOD_PULSE_SPEED + phase).sin()
+    } else {
+      1.0
+    };
+    transform.scale = Vec3::new(scale, scale, 1.0);
+  }
+}
+
+// All normal food shares one material, so it changes color together.
+pub fn rainbow_food(
+  time: Res<Time>,
+  rainbow: Res<RainbowFood>,
+  config: Res<MaterialsConfig>,
+  materials: Res<Materials>,
+  mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+  if !rainbow.0 { return; }
+  let (hue, saturation, lightness, alpha) = match config.food.as_hsla() {
+    Color::Hsla { hue, saturation, lightness, alpha } => (hue, saturation, lightness, alpha),
+    _ => return,
+  };
+  let shift = time.seconds_since_startup() as f32 * RAINBOW_FOOD_SPEED;
+  if let Some(material) = color_materials.get_mut(&materials.food_material) {
+    material.color = Color::hsla((hue + shift) % 360.0, saturation, lightness, alpha);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bevy::window::WindowId;
+
+  use super::*;
+
+  fn particle_velocities(seed: u64) -> Vec<Vec2> {
+    let mut world = World::default();
+    let mut windows = Windows::default();
+    let descriptor = WindowDescriptor { width: 400.0, height: 400.0, ..Default::default() };
+    windows.add(Window::new(WindowId::primary(), &descriptor, 400, 400, 1.0, None));
+    world.insert_resource(windows);
+    world.insert_resource(ArenaSize { width: 10, height: 10 });
+    world.insert_resource(ScalingMode::default());
+    world.insert_resource(ParticleConfig::default());
+    world.insert_resource(Materials::default());
+    world.insert_resource(CosmeticRng::from_seed(seed));
+    world.insert_resource(Events::<ScoreEvent>::default());
+    world.get_resource_mut::<Events<ScoreEvent>>().unwrap().send(ScoreEvent {
+      player: Player::One,
+      delta: FoodKind::Normal.delta(),
+      position: Position::new(4, 4),
+      kind: FoodKind::Normal,
+    });
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(spawn_particles.system());
+    stage.run(&mut world);
+    world.query::<&Particle>().iter(&world).map(|particle| particle.velocity).collect()
+  }
+
+  #[test]
+  fn eating_bursts_the_same_particles_for_the_same_seed() {
+    let config = ParticleConfig::default();
+    let velocities = particle_velocities(5);
+    assert_eq!(velocities.len(), config.count as usize);
+    for velocity in velocities.iter() {
+      assert!((velocity.length() - config.speed).abs() < 0.01);
+    }
+    assert_eq!(particle_velocities(5), velocities);
+    assert_ne!(particle_velocities(6), velocities);
+  }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::game::*;
+
+// Food closer than this many tiles to the head is easy enough to spot.
+pub const FOOD_ARROW_HIDE_DISTANCE: i32 = 2;
+// How far from the head's center the arrow sits, in tiles.
+pub const FOOD_ARROW_OFFSET: f32 = 1.2;
+
+// Whether an arrow next to player one's head points at the nearest food.
+#[derive(Default)]
+pub struct ShowFoodArrow(pub bool);
+
+pub struct FoodArrow;
+
+pub fn spawn_food_arrow(mut commands: Commands, materials: Res<Materials>) {
+  commands
+    .spawn_bundle(SpriteBundle {
+      material: materials.food_material.clone(),
+      visible: Visible { is_visible: false, is_transparent: false },
+      ..Default::default()
+    })
+    .insert(FoodArrow);
+}
+
+// Runs after the head has been placed for the frame. The arrow is a thin bar
+// along its own y axis, turned to point from the head towards the food.
+pub fn food_arrow(
+  show: Res<ShowFoodArrow>,
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  heads: Query<(&Position, &Transform, &Player), (With<SnakeHead>, Without<FoodArrow>)>,
+  food: Query<&Position, With<Food>>,
+  mut arrows: Query<(&mut Transform, &mut Sprite, &mut Visible), With<FoodArrow>>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let head = heads.iter().find(|(_, _, player)| **player == Player::One);
+  let target = head.and_then(|(head_position, head_transform, _)| {
+    let distance = |position: &Position| {
+      (position.x() - head_position.x()).abs() + (position.y() - head_position.y()).abs()
+    };
+    let nearest = food.iter().min_by_key(|position| distance(position))?;
+    if distance(nearest) <= FOOD_ARROW_HIDE_DISTANCE { return None; }
+    Some((head_transform.translation, *nearest))
+  });
+  let board = board_size(window, &arena, *scaling);
+  let tile = Vec2::new(board.x / arena.width as f32, board.y / arena.height as f32);
+  for (mut transform, mut sprite, mut visible) in arrows.iter_mut() {
+    let (head, nearest) = match target {
+      Some(target) if show.0 => target,
+      _ => {
+        visible.is_visible = false;
+        continue;
+      }
+    };
+    let towards = (tile_translation(&nearest, board, &arena) - head).truncate().normalize_or_zero();
+    let offset = towards * tile * FOOD_ARROW_OFFSET;
+    transform.translation = Vec3::new(head.x + offset.x, head.y + offset.y, 1.0);
+    let angle = towards.y.atan2(towards.x) - std::f32::consts::FRAC_PI_2;
+    transform.rotation = Quat::from_rotation_z(angle);
+    sprite.size = Vec2::new(tile.x * 0.2, tile.y * 0.6);
+    visible.is_visible = true;
+  }
+}
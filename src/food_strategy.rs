@@ -0,0 +1,66 @@
+use rand::prelude::*;
+
+use crate::game::Position;
+
+// Decides where `food_spawner` puts new food. `free` is every empty tile, row
+// by row, and is never empty. Draw only from `rng` so seeded runs still replay.
+pub trait FoodStrategy: Send + Sync {
+  fn pick(&mut self, free: &[Position], rng: &mut StdRng) -> Position;
+
+  // Called with the players' heads before each round of spawning.
+  fn observe(&mut self, _heads: &[Position]) {}
+}
+
+// Any free tile, all equally likely.
+#[derive(Default)]
+pub struct UniformStrategy;
+
+impl FoodStrategy for UniformStrategy {
+  fn pick(&mut self, free: &[Position], rng: &mut StdRng) -> Position {
+    *free.choose(rng).unwrap()
+  }
+}
+
+// Picks from the free tiles in the farther half away from the nearest head.
+#[derive(Default)]
+pub struct FarFromHead {
+  heads: Vec<Position>,
+}
+
+impl FoodStrategy for FarFromHead {
+  fn pick(&mut self, free: &[Position], rng: &mut StdRng) -> Position {
+    let distance = |position: &Position| {
+      self.heads
+        .iter()
+        .map(|head| (head.x() - position.x()).abs() + (head.y() - position.y()).abs())
+        .min()
+        .unwrap_or(0)
+    };
+    let farthest = free.iter().map(distance).max().unwrap_or(0);
+    let far = free
+      .iter()
+      .copied()
+      .filter(|position| distance(position) * 2 >= farthest)
+      .collect::<Vec<Position>>();
+    *far.choose(rng).unwrap()
+  }
+
+  fn observe(&mut self, heads: &[Position]) {
+    self.heads = heads.to_vec();
+  }
+}
+
+// The strategy in use. Insert one before adding the plugin to change it.
+pub struct FoodPlacement(pub Box<dyn FoodStrategy>);
+
+impl FoodPlacement {
+  pub fn new(strategy: impl FoodStrategy + 'static) -> Self {
+    Self(Box::new(strategy))
+  }
+}
+
+impl Default for FoodPlacement {
+  fn default() -> Self {
+    Self::new(UniformStrategy)
+  }
+}
@@ -1,30 +1,357 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Add;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::core::Stopwatch;
+use bevy::ecs::schedule::ShouldRun;
 use bevy::prelude::*;
-use rand::prelude::random;
+use bevy::window::WindowFocused;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::AiSnakeState;
+use crate::combo::Combo;
+use crate::high_score::{high_score_path, save_high_score, HighScore};
+use crate::food_strategy::FoodPlacement;
+use crate::level::LevelData;
+use crate::power_ups::{SlowMo, SLOW_MO_FACTOR};
+use crate::replay::{Replay, ReplayMode};
+use crate::versus::RoundOver;
 
 pub struct Food;
 
+pub struct PoisonFood;
+
+// Rare food worth extra points that disappears if it isn't eaten in time.
+// Bonus food entities also carry `Food`, so they grow the snake as usual.
+pub struct BonusFood(pub Timer);
+
+pub struct Wall;
+
+// Big food covers a 2x2 block of tiles, each a `BigFoodPart` pointing back at
+// the `BigFood`. Every bite takes one tile away, and the last one eats it.
+pub struct BigFood {
+  pub bites_remaining: u32,
+}
+
+pub struct BigFoodPart(pub Entity);
+
+// On every power-up pickup, next to the component saying which one it is.
+pub struct PowerUp;
+
+// Stepping onto a portal takes the head to the other portal with the same id.
+pub struct Portal {
+  pub id: u8,
+}
+
+// How many segments eating a food adds, one if it doesn't have this.
+pub struct GrowthAmount(pub u32);
+
+// Food with a lifetime disappears once it runs out, without granting points.
+pub struct Lifetime(pub Timer);
+
+// Food left uneaten until this runs out rots where it lies.
+pub struct RottenTimer(pub Timer);
+
+// Rotten food is a `Wall` until its `Lifetime` runs out, so touching it is
+// deadly.
+pub struct Rotten;
+
 pub struct SnakeHead {
   pub input_direction: Direction,
   pub movement_direction: Direction,
+  pub input_queue: VecDeque<Direction>,
+}
+
+impl SnakeHead {
+  pub fn new(direction: Direction) -> Self {
+    Self {
+      input_direction: direction,
+      movement_direction: direction,
+      input_queue: VecDeque::with_capacity(INPUT_QUEUE_LENGTH),
+    }
+  }
+
+  // Where the snake will be heading once every queued turn has been made.
+  pub fn queued_direction(&self) -> Direction {
+    self.input_queue.back().copied().unwrap_or(self.movement_direction)
+  }
+
+  // Turns are validated against the last queued direction rather than the
+  // current movement direction, so two quick turns can never add up to a
+  // 180 degree reversal.
+  pub fn queue_direction(&mut self, direction: Direction) {
+    let last_direction = self.queued_direction();
+    if self.input_queue.len() < INPUT_QUEUE_LENGTH &&
+        direction != last_direction &&
+        direction != last_direction.opposite() {
+      self.input_queue.push_back(direction);
+    }
+  }
 }
 
 pub struct SnakeSegment;
 
+// The camera the board is drawn with, as opposed to the UI one.
+pub struct MainCamera;
+
+// The tile a snake tile moved from on the last tick, so it can slide from there
+// to its current `Position` while the movement timer runs.
+pub struct PrevPosition {
+  pub position: Position,
+  seen: Position,
+}
+
+impl PrevPosition {
+  pub fn new(position: Position) -> Self {
+    Self { position, seen: position }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Player {
+  One,
+  Two,
+}
+
+// The body of every player's snake, head first.
+#[derive(Default)]
+pub struct SnakeSegments(HashMap<Player, Vec<Entity>>);
+
+impl SnakeSegments {
+  pub fn get(&self, player: Player) -> Option<&[Entity]> {
+    self.0.get(&player).map(Vec::as_slice)
+  }
+
+  // 0 for a player without a snake.
+  pub fn len(&self, player: Player) -> usize {
+    self.get(player).map_or(0, <[Entity]>::len)
+  }
+
+  pub fn is_empty(&self, player: Player) -> bool {
+    self.len(player) == 0
+  }
+
+  pub fn head(&self, player: Player) -> Option<Entity> {
+    self.get(player)?.first().copied()
+  }
+
+  pub fn tail(&self, player: Player) -> Option<Entity> {
+    self.get(player)?.last().copied()
+  }
+
+  // Every player's snake, in no particular order.
+  pub fn iter(&self) -> impl Iterator<Item = (Player, &[Entity])> {
+    self.0.iter().map(|(&player, segments)| (player, segments.as_slice()))
+  }
+
+  pub fn insert(&mut self, player: Player, segments: Vec<Entity>) {
+    self.0.insert(player, segments);
+  }
+
+  pub fn clear(&mut self) {
+    self.0.clear();
+  }
+}
+
+// Who's on the board. In co-op a second snake, steered with
+// `AlternateKeyBindings`, plays alongside the first: both share the one
+// `Score` and the lives, the snakes collide with each other, and a crash by
+// either of them ends the run (or costs a life) for both. In versus the two
+// play a match of rounds against each other instead, see `MatchState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+  Solo,
+  CoOp,
+  Versus,
+}
+
+impl Default for PlayMode {
+  fn default() -> Self {
+    Self::Solo
+  }
+}
+
+impl PlayMode {
+  pub fn two_players(&self) -> bool {
+    *self != Self::Solo
+  }
+}
+
+// Shades the players' snakes from head to tail instead of one flat color.
+pub struct GradientSegments(pub bool);
+
+impl Default for GradientSegments {
+  fn default() -> Self {
+    Self(true)
+  }
+}
+
+// One material per player and segment index, reused as snakes grow and shrink.
 #[derive(Default)]
-pub struct SnakeSegments(Vec<Entity>);
+pub struct GradientMaterials(HashMap<(Player, usize), Handle<ColorMaterial>>);
 
+#[derive(Default)]
 pub struct Materials {
   pub head_material: Handle<ColorMaterial>,
   pub segment_material: Handle<ColorMaterial>,
   pub food_material: Handle<ColorMaterial>,
+  pub wall_material: Handle<ColorMaterial>,
+  pub ai_head_material: Handle<ColorMaterial>,
+  pub ai_segment_material: Handle<ColorMaterial>,
+  pub player_two_head_material: Handle<ColorMaterial>,
+  pub player_two_segment_material: Handle<ColorMaterial>,
+  pub poison_material: Handle<ColorMaterial>,
+  pub bonus_material: Handle<ColorMaterial>,
+  pub grid_material: Handle<ColorMaterial>,
+  pub portal_material: Handle<ColorMaterial>,
+  pub big_food_material: Handle<ColorMaterial>,
+  pub magnet_material: Handle<ColorMaterial>,
+  pub ghost_material: Handle<ColorMaterial>,
+  pub death_material: Handle<ColorMaterial>,
+  pub border_material: Handle<ColorMaterial>,
+  pub rotten_material: Handle<ColorMaterial>,
+  pub slow_mo_material: Handle<ColorMaterial>,
+  pub assist_material: Handle<ColorMaterial>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+// The colors `Materials` is built from at startup. Inserting one before the
+// plugin is added, or passing one to `with_materials`, recolors everything.
+#[derive(Clone)]
+pub struct MaterialsConfig {
+  pub head: Color,
+  pub segment: Color,
+  pub food: Color,
+  pub wall: Color,
+  pub ai_head: Color,
+  pub ai_segment: Color,
+  pub player_two_head: Color,
+  pub player_two_segment: Color,
+  pub poison: Color,
+  pub bonus: Color,
+  pub grid: Color,
+  pub portal: Color,
+  pub big_food: Color,
+  pub magnet: Color,
+  pub ghost: Color,
+  pub death: Color,
+  pub border: Color,
+  pub rotten: Color,
+  pub slow_mo: Color,
+  // Drawn over the tiles, so it wants to be see-through.
+  pub assist: Color,
+}
+
+impl Materials {
+  // Points every material at the colors in `config`, so everything already
+  // drawn with them changes color too.
+  pub fn recolor(&self, config: &MaterialsConfig, assets: &mut Assets<ColorMaterial>) {
+    let colors = [
+      (&self.head_material, config.head),
+      (&self.segment_material, config.segment),
+      (&self.food_material, config.food),
+      (&self.wall_material, config.wall),
+      (&self.ai_head_material, config.ai_head),
+      (&self.ai_segment_material, config.ai_segment),
+      (&self.player_two_head_material, config.player_two_head),
+      (&self.player_two_segment_material, config.player_two_segment),
+      (&self.poison_material, config.poison),
+      (&self.bonus_material, config.bonus),
+      (&self.grid_material, config.grid),
+      (&self.portal_material, config.portal),
+      (&self.big_food_material, config.big_food),
+      (&self.magnet_material, config.magnet),
+      (&self.ghost_material, config.ghost),
+      (&self.death_material, config.death),
+      (&self.border_material, config.border),
+      (&self.rotten_material, config.rotten),
+      (&self.slow_mo_material, config.slow_mo),
+      (&self.assist_material, config.assist),
+    ];
+    for (handle, color) in colors.iter() {
+      if let Some(material) = assets.get_mut(*handle) {
+        material.color = *color;
+      }
+    }
+  }
+}
+
+impl Default for MaterialsConfig {
+  fn default() -> Self {
+    Self {
+      head: Color::rgb(0.7, 0.7, 0.7),
+      segment: Color::rgb(0.3, 0.3, 0.3),
+      food: Color::rgb(1.0, 0.0, 1.0),
+      wall: Color::rgb(0.35, 0.35, 0.5),
+      ai_head: Color::rgb(0.9, 0.6, 0.2),
+      ai_segment: Color::rgb(0.5, 0.3, 0.1),
+      player_two_head: Color::rgb(0.3, 0.8, 0.8),
+      player_two_segment: Color::rgb(0.1, 0.4, 0.4),
+      poison: Color::rgb(0.2, 0.8, 0.1),
+      bonus: Color::rgb(1.0, 0.85, 0.1),
+      grid: Color::rgb(0.1, 0.1, 0.1),
+      portal: Color::rgb(0.6, 0.2, 0.9),
+      big_food: Color::rgb(1.0, 0.3, 0.6),
+      magnet: Color::rgb(0.75, 0.75, 0.9),
+      ghost: Color::rgb(0.9, 0.9, 0.9),
+      death: Color::rgb(0.9, 0.1, 0.1),
+      border: Color::rgb(0.5, 0.5, 0.5),
+      rotten: Color::rgb(0.45, 0.35, 0.1),
+      slow_mo: Color::rgb(0.4, 0.7, 1.0),
+      assist: Color::rgba(1.0, 0.2, 0.2, 0.3),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
   x: i32,
   y: i32,
 }
 
+impl Position {
+  pub fn new(x: i32, y: i32) -> Self {
+    Self { x, y }
+  }
+
+  pub fn x(&self) -> i32 {
+    self.x
+  }
+
+  pub fn y(&self) -> i32 {
+    self.y
+  }
+
+  pub fn neighbor(&self, direction: Direction) -> Self {
+    *self + direction
+  }
+
+  pub fn in_arena(&self, arena: &ArenaSize) -> bool {
+    self.x >= 0 && self.y >= 0 && (self.x as u32) < arena.width && (self.y as u32) < arena.height
+  }
+
+  pub fn is_adjacent(&self, other: &Position) -> bool {
+    (self.x - other.x).abs() + (self.y - other.y).abs() <= 1
+  }
+
+  pub fn wrapped(&self, arena: &ArenaSize) -> Self {
+    Self {
+      x: self.x.rem_euclid(arena.width as i32),
+      y: self.y.rem_euclid(arena.height as i32),
+    }
+  }
+}
+
+impl Add<Direction> for Position {
+  type Output = Self;
+
+  fn add(self, direction: Direction) -> Self {
+    let (dx, dy) = direction.to_offset();
+    Self { x: self.x + dx, y: self.y + dy }
+  }
+}
+
 pub struct Size {
   width: f32,
   height: f32,
@@ -37,9 +364,17 @@ impl Size {
       height: x,
     }
   }
+
+  pub fn width(&self) -> f32 {
+    self.width
+  }
+
+  pub fn height(&self) -> f32 {
+    self.height
+  }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
   Left,
   Up,
@@ -56,6 +391,69 @@ impl Direction {
       Self::Down => Self::Up,
     }
   }
+
+  pub fn turn_left(&self) -> Self {
+    match self {
+      Self::Left => Self::Down,
+      Self::Up => Self::Left,
+      Self::Right => Self::Up,
+      Self::Down => Self::Right,
+    }
+  }
+
+  pub fn turn_right(&self) -> Self {
+    self.turn_left().opposite()
+  }
+
+  pub fn to_offset(&self) -> (i32, i32) {
+    match self {
+      Self::Left => (-1, 0),
+      Self::Up => (0, 1),
+      Self::Right => (1, 0),
+      Self::Down => (0, -1),
+    }
+  }
+
+  // The direction of a single step from `from` to `to`, if they're neighbours.
+  pub fn between(from: Position, to: Position) -> Option<Self> {
+    [Self::Left, Self::Up, Self::Right, Self::Down]
+      .iter()
+      .copied()
+      .find(|&direction| from + direction == to)
+  }
+
+  // Rotation around z from a sprite pointing up to one pointing this way.
+  pub fn angle(&self) -> f32 {
+    match self {
+      Self::Left => std::f32::consts::FRAC_PI_2,
+      Self::Up => 0.0,
+      Self::Right => -std::f32::consts::FRAC_PI_2,
+      Self::Down => std::f32::consts::PI,
+    }
+  }
+}
+
+// The game starts in `Menu`. Transitions:
+// - Menu -> Playing: Return pressed on the title screen (`start_game`).
+// - Playing -> Dying: the snake died (`game_over`). `Dying` is pushed on top of
+//   `Playing` while the death animation runs.
+// - Dying -> Playing: a life was lost and the snake respawns (`dying`).
+// - Dying -> GameOver: that was the last life (`dying`).
+// - Playing -> Won: the snake filled the whole board (`game_won`).
+// - GameOver/Won -> Playing: Return pressed on the end screen (`start_game`).
+// - Playing <-> Paused: `Paused` is pushed on top of `Playing` so resuming
+//   doesn't run the `on_enter(Playing)` setup again. Losing window focus
+//   pauses too (`auto_pause`), but only the pause key resumes.
+// Entering `Playing` clears the board and spawns a fresh level, snake and food.
+// Escape quits from any state (`exit_on_escape`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+  Menu,
+  Playing,
+  Paused,
+  Dying,
+  GameOver,
+  Won,
 }
 
 #[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
@@ -66,212 +464,2898 @@ pub enum SnakeMovement {
   Growth,
 }
 
+pub struct KeyBindings {
+  pub up: KeyCode,
+  pub down: KeyCode,
+  pub left: KeyCode,
+  pub right: KeyCode,
+}
+
+impl Default for KeyBindings {
+  fn default() -> Self {
+    Self {
+      up: KeyCode::Up,
+      down: KeyCode::Down,
+      left: KeyCode::Left,
+      right: KeyCode::Right,
+    }
+  }
+}
+
+impl KeyBindings {
+  pub fn wasd() -> Self {
+    Self {
+      up: KeyCode::W,
+      down: KeyCode::S,
+      left: KeyCode::A,
+      right: KeyCode::D,
+    }
+  }
+
+  pub fn keys(&self) -> [(KeyCode, Direction); 4] {
+    [
+      (self.left, Direction::Left),
+      (self.right, Direction::Right),
+      (self.up, Direction::Up),
+      (self.down, Direction::Down),
+    ]
+  }
+}
+
+// With `Relative` controls the left and right keys turn the snake relative to
+// where it's heading and the up and down keys do nothing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ControlScheme {
+  Absolute,
+  Relative,
+}
+
+impl Default for ControlScheme {
+  fn default() -> Self {
+    Self::Absolute
+  }
+}
+
+// WASD by default. Player two steers with these in two-player mode, otherwise
+// they're checked after `KeyBindings` for player one.
+pub struct AlternateKeyBindings(pub Option<KeyBindings>);
+
+impl Default for AlternateKeyBindings {
+  fn default() -> Self {
+    Self(Some(KeyBindings::wasd()))
+  }
+}
+
 #[derive(Default)]
-pub struct LastTailPosition(Option<Position>);
+pub struct LastTailPosition(HashMap<Player, Position>);
 
-pub struct GrowthEvent;
-pub struct GameOverEvent;
+#[derive(Default)]
+pub struct Score(pub u32);
 
-pub const ARENA_WIDTH: u32 = 10;
-pub const ARENA_HEIGHT: u32 = 10;
+// Time spent playing this run. It doesn't run while the game is paused.
+#[derive(Default)]
+pub struct SurvivalTime(pub Stopwatch);
 
-pub fn spawn_segment(
-  mut commands: Commands,
-  material: Handle<ColorMaterial>,
-  position: Position
-) -> Entity {
-  commands
-    .spawn_bundle(SpriteBundle { material, ..Default::default() })
-    .insert(SnakeSegment)
-    .insert(position)
-    .insert(Size::square(0.65))
-    .id()
+// Only present in time attack mode, where the run ends once `limit` has passed.
+pub struct TimeAttack {
+  pub limit: Duration,
 }
 
-pub fn spawn_snake(
-  mut commands: Commands,
-  mut segments: ResMut<SnakeSegments>,
-  materials: Res<Materials>
-) {
-  segments.0 = vec![
-    commands
-      .spawn_bundle(SpriteBundle {
-        material: materials.head_material.clone(),
-        sprite: Sprite::new(Vec2::new(10.0, 10.0)),
-        ..Default::default()
-      })
-      .insert(SnakeHead {
-        input_direction: Direction::Up,
-        movement_direction: Direction::Up
-      })
-      .insert(SnakeSegment)
-      .insert(Position { x: 3, y: 3 })
-      .insert(Size::square(0.8))
-      .id(),
-    spawn_segment(
-      commands,
-      materials.segment_material.clone(),
-      Position { x: 3, y: 2 },
-    ),
-  ]
+// Only present when hunger is on. It runs down between meals, and the snake
+// starves if it runs out.
+pub struct Hunger(pub Timer);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HungerPenalty {
+  // Starving costs a life.
+  Die,
+  // Starving costs a segment and the hunger starts over.
+  Shrink,
 }
 
-pub fn snake_movement_input(input: Res<Input<KeyCode>>, mut heads: Query<&mut SnakeHead>) {
-  if let Some(mut head) = heads.iter_mut().next() {
-    let new_direction = {
-      if input.pressed(KeyCode::Left) { Direction::Left }
-      else if input.pressed(KeyCode::Right) { Direction::Right}
-      else if input.pressed(KeyCode::Up) { Direction::Up }
-      else if input.pressed(KeyCode::Down) { Direction::Down }
-      else { head.input_direction }
-    };
+impl Default for HungerPenalty {
+  fn default() -> Self {
+    Self::Die
+  }
+}
 
-    if new_direction != head.movement_direction.opposite() {
-      head.input_direction = new_direction;
-    }
+// Lives left in this run, counting the current one. A fresh game starts with
+// `StartingLives`.
+#[derive(Default)]
+pub struct Lives(pub u32);
+
+pub struct StartingLives(pub u32);
+
+impl Default for StartingLives {
+  fn default() -> Self {
+    Self(3)
   }
 }
 
-pub fn snake_movement(
-  segments: Res<SnakeSegments>,
-  mut last_tail_position: ResMut<LastTailPosition>,
-  mut heads: Query<(Entity, &mut SnakeHead)>,
-  mut positions: Query<&mut Position>,
-  mut game_over_writer: EventWriter<GameOverEvent>,
-) {
-  if let Some((head_entity, mut head)) = heads.iter_mut().next() {
-    let segment_positions = segments.0
-      .iter()
-      .map(|&entity| *positions.get_mut(entity).unwrap())
-      .collect::<Vec<Position>>();
-    let mut head_position = positions.get_mut(head_entity).unwrap();
-    match head.input_direction {
-      Direction::Left => { head_position.x -= 1; },
-      Direction::Up => { head_position.y += 1; },
-      Direction::Right => { head_position.x += 1; },
-      Direction::Down => { head_position.y -= 1; },
-    }
-    head.movement_direction = head.input_direction;
-    if head_position.x < 0 ||
-        head_position.x as u32 >= ARENA_WIDTH ||
-        head_position.y < 0 ||
-        head_position.y as u32 >= ARENA_HEIGHT {
-      game_over_writer.send(GameOverEvent);
-    }
-    if segment_positions.contains(&head_position) {
-      game_over_writer.send(GameOverEvent);
-    }
-    segment_positions
-      .iter()
-      .zip(segments.0.iter().skip(1))
-      .for_each(|(&position, &segment)| {
-        *positions.get_mut(segment).unwrap() = position;
-      });
-    last_tail_position.0 = segment_positions.last().copied();
+// The shortest a snake can get. Anything that takes segments off stops here,
+// or crashes the snake if it can't.
+pub struct MinLength(pub usize);
+
+impl Default for MinLength {
+  fn default() -> Self {
+    Self(2)
   }
 }
 
-pub fn snake_eating(
-  mut commands: Commands,
-  mut growth_writer: EventWriter<GrowthEvent>,
-  food_positions: Query<(&Position, Entity), With<Food>>,
-  head_positions: Query<&Position, With<SnakeHead>>,
-) {
-  if let Some(head_position) = head_positions.iter().next() {
-    for (food_position, food_entity) in food_positions.iter() {
-      if food_position == head_position {
-        commands.entity(food_entity).despawn();
-        growth_writer.send(GrowthEvent);
-      }
-    }
+// A snapshot of the current run for UI and other plugins to read. `length` is
+// player one's.
+#[derive(Default)]
+pub struct GameStats {
+  pub length: usize,
+  pub score: u32,
+  pub ticks_survived: u64,
+  // Simulation steps since the countdown finished, whether the snakes moved on
+  // them or not.
+  pub steps_simulated: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ArenaSize {
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Default for ArenaSize {
+  fn default() -> Self {
+    Self { width: 10, height: 10 }
   }
 }
 
-pub fn snake_growth(
-  commands: Commands,
-  last_tail_position: Res<LastTailPosition>,
-  mut segments: ResMut<SnakeSegments>,
-  mut growth_reader: EventReader<GrowthEvent>,
-  materials: Res<Materials>,
-) {
-  if growth_reader.iter().next().is_some() {
-    segments.0.push(spawn_segment(
-      commands,
-      materials.segment_material.clone(),
-      last_tail_position.0.unwrap(),
-    ));
+// What happens when the snake runs into an edge of the arena: it dies, comes
+// back through the opposite edge, or turns around and carries on tail first.
+#[derive(Clone, Copy, PartialEq)]
+pub enum WallBehavior {
+  Die,
+  Wrap,
+  Bounce,
+}
+
+impl Default for WallBehavior {
+  fn default() -> Self {
+    Self::Die
   }
 }
 
-pub fn game_over(
-  mut commands: Commands,
-  mut reader: EventReader<GameOverEvent>,
-  materials: Res<Materials>,
-  segments_res: ResMut<SnakeSegments>,
-  food: Query<Entity, With<Food>>,
-  segments: Query<Entity, With<SnakeSegment>>,
-) {
-  if reader.iter().next().is_some() {
-    for entity in food.iter().chain(segments.iter()) {
-      commands.entity(entity).despawn();
+// What each edge of the arena does, so e.g. only the sides can wrap. Top is
+// the edge at the highest `y`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct EdgeConfig {
+  pub top: WallBehavior,
+  pub bottom: WallBehavior,
+  pub left: WallBehavior,
+  pub right: WallBehavior,
+}
+
+impl Default for EdgeConfig {
+  fn default() -> Self {
+    Self::all(WallBehavior::default())
+  }
+}
+
+impl EdgeConfig {
+  pub fn all(behavior: WallBehavior) -> Self {
+    Self { top: behavior, bottom: behavior, left: behavior, right: behavior }
+  }
+
+  // The behavior of the edge `position` is past, `None` while it's inside the
+  // arena. A single step can only ever cross one edge.
+  pub fn crossed(&self, position: Position, arena: &ArenaSize) -> Option<WallBehavior> {
+    if position.x < 0 {
+      Some(self.left)
+    } else if position.x >= arena.width as i32 {
+      Some(self.right)
+    } else if position.y < 0 {
+      Some(self.bottom)
+    } else if position.y >= arena.height as i32 {
+      Some(self.top)
+    } else {
+      None
     }
-    spawn_snake(commands, segments_res, materials);
   }
 }
 
-pub fn size_scaling(windows: Res<Windows>, mut q: Query<(&Size, &mut Sprite)>) {
-  let window = windows.get_primary().expect("Couldn't find primary window!");
-  for (sprite_size, mut sprite) in q.iter_mut() {
-    sprite.size = Vec2::new(
-      window.width() / (ARENA_WIDTH as f32) * sprite_size.width,
-      window.height() / (ARENA_HEIGHT as f32) * sprite_size.height,
-    );
+// How often the poison, bonus food, big food and power-up spawners each get a
+// chance to spawn something, counted in simulation steps once the countdown is
+// over so a seeded run spawns things on the same ticks every time.
+pub struct SpawnTimer(pub Timer);
+
+impl Default for SpawnTimer {
+  fn default() -> Self {
+    Self(Timer::from_seconds(SPAWN_INTERVAL, true))
   }
 }
 
-pub fn position_translation(windows: Res<Windows>, mut q: Query<(&Position, &mut Transform)>) {
-  let window = windows.get_primary().expect("Couldn't find primary window!");
+// How much regular food is kept on the board at once.
+pub struct FoodCount(pub u32);
 
-  fn convert_dimension(dimension: f32, window_dimension: f32, arena_dimension: f32) -> f32 {
-    let tile_dimension = window_dimension / arena_dimension;
-    dimension * tile_dimension - window_dimension / 2.0 + tile_dimension / 2.0
+impl Default for FoodCount {
+  fn default() -> Self {
+    Self(1)
   }
+}
 
-  for (pos, mut transform) in q.iter_mut() {
-    transform.translation = Vec3::new(
-      convert_dimension(pos.x as f32, window.width(), ARENA_WIDTH as f32),
-      convert_dimension(pos.y as f32, window.height(), ARENA_HEIGHT as f32),
-      0.0,
-    );
+// How many segments regular food adds. Every food put on the board gets a
+// `GrowthAmount` of this much.
+pub struct FoodGrowth(pub u32);
+
+impl Default for FoodGrowth {
+  fn default() -> Self {
+    Self(1)
   }
 }
 
-pub fn food_spawner(
-  mut commands: Commands,
-  materials: Res<Materials>,
-  food_entities: Query<Entity, With<Food>>,
-  segment_entities: Query<Entity, With<SnakeSegment>>,
-  positions: Query<&Position>,
-) {
-  let position = loop {
-    let position = Position {
-      x: (random::<f32>() * ARENA_WIDTH as f32) as i32,
-      y: (random::<f32>() * ARENA_HEIGHT as f32) as i32,
-    };
-    let taken_positions = food_entities
-      .iter()
-      .chain(segment_entities.iter())
-      .map(|entity| *positions.get(entity).unwrap())
-      .collect::<Vec<Position>>();
-    if !taken_positions.contains(&position) { break position; }
-  };
+// Seconds before regular food expires and moves elsewhere.
+pub struct FoodLifetime(pub f32);
 
-  commands
-    .spawn_bundle(SpriteBundle {
-      material: materials.food_material.clone(),
-      ..Default::default()
-    })
-    .insert(Food)
-    .insert(position)
-    .insert(Size::square(0.8));
+impl Default for FoodLifetime {
+  fn default() -> Self {
+    Self(10.0)
+  }
+}
+
+// Seconds before regular food rots, if it does at all. Food whose lifetime is
+// shorter than this expires before it gets the chance.
+#[derive(Default)]
+pub struct RotTime(pub Option<f32>);
+
+// What happens when the snake's own body is on the far side of a portal: the
+// teleport is cancelled, or the snake dies if this is set.
+#[derive(Default)]
+pub struct FatalBlockedPortals(pub bool);
+
+// Chance of a poison food being spawned every time the food spawner runs.
+pub struct PoisonChance(pub f32);
+
+impl Default for PoisonChance {
+  fn default() -> Self {
+    Self(0.25)
+  }
+}
+
+// Chance of a bonus food being spawned every time the food spawner runs.
+pub struct BonusChance(pub f32);
+
+impl Default for BonusChance {
+  fn default() -> Self {
+    Self(0.1)
+  }
+}
+
+// Chance of a big food being spawned every time the food spawner runs.
+pub struct BigFoodChance(pub f32);
+
+impl Default for BigFoodChance {
+  fn default() -> Self {
+    Self(0.05)
+  }
+}
+
+// Everything random about how a run plays out draws from this, so a fixed seed
+// always plays out the same board.
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+  pub fn from_seed(seed: u64) -> Self {
+    Self(StdRng::seed_from_u64(seed))
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> Self {
+    Self(StdRng::from_entropy())
+  }
+}
+
+// The snake spawns with its head on `start` facing `direction`, with the rest
+// of its `length` trailing out behind it.
+#[derive(Clone, Copy)]
+pub struct SnakeSpawnConfig {
+  pub start: Position,
+  pub length: u32,
+  pub direction: Direction,
+}
+
+impl Default for SnakeSpawnConfig {
+  fn default() -> Self {
+    Self {
+      start: Position::new(3, 3),
+      length: 2,
+      direction: Direction::Up,
+    }
+  }
+}
+
+// How much of its tile each part of a snake covers.
+#[derive(Clone, Copy)]
+pub struct VisualConfig {
+  pub head_scale: f32,
+  pub segment_scale: f32,
+}
+
+impl Default for VisualConfig {
+  fn default() -> Self {
+    Self { head_scale: 0.8, segment_scale: 0.65 }
+  }
+}
+
+impl SnakeSpawnConfig {
+  // Head first.
+  pub fn positions(&self) -> Vec<Position> {
+    let behind = self.direction.opposite();
+    let mut positions = vec![self.start];
+    for _ in 1..self.length.max(1) {
+      let last = *positions.last().unwrap();
+      positions.push(last + behind);
+    }
+    positions
+  }
+
+  pub fn fits(&self, arena: &ArenaSize, walls: &[Position]) -> bool {
+    self.positions()
+      .iter()
+      .all(|position| position.in_arena(arena) && !walls.contains(position))
+  }
+
+  // The same spawn, turned away from a deadly edge or a wall right in front of it
+  // so the first tick isn't fatal. Edges that wrap or bounce are safe to face.
+  // Turning around is tried first, then either side. Left as it is if no way out
+  // leaves the snake fitting on the board.
+  pub fn facing_safely(&self, arena: &ArenaSize, edges: &EdgeConfig, walls: &[Position]) -> Self {
+    let is_safe = |spawn: &Self| {
+      let next = spawn.start + spawn.direction;
+      let clear = match edges.crossed(next, arena) {
+        None => !walls.contains(&next),
+        Some(WallBehavior::Wrap) => !walls.contains(&next.wrapped(arena)),
+        Some(WallBehavior::Bounce) => true,
+        Some(WallBehavior::Die) => false,
+      };
+      clear && spawn.fits(arena, walls)
+    };
+    if is_safe(self) { return *self; }
+    let direction = self.direction;
+    let candidates = [direction.opposite(), direction.turn_left(), direction.turn_right()];
+    match candidates.iter().map(|&direction| Self { direction, ..*self }).find(is_safe) {
+      Some(spawn) => {
+        warn!("Snake spawn faces {:?} into a wall, using {:?}", direction, spawn.direction);
+        spawn
+      }
+      None => *self,
+    }
+  }
+
+  // Flipped left to right across the arena.
+  pub fn mirrored(&self, arena: &ArenaSize) -> Self {
+    let direction = match self.direction {
+      Direction::Left | Direction::Right => self.direction.opposite(),
+      direction => direction,
+    };
+    Self {
+      start: Position { x: arena.width as i32 - 1 - self.start.x, y: self.start.y },
+      direction,
+      ..*self
+    }
+  }
+}
+
+// The snake waits for this to finish at the start of every run. Input is still
+// taken meanwhile, so the first turns can be queued up.
+pub struct Countdown(pub Timer);
+
+// Starts out finished so there's nothing to count down before the first run.
+impl Default for Countdown {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(COUNTDOWN_SECONDS, false);
+    timer.tick(Duration::from_secs_f32(COUNTDOWN_SECONDS));
+    Self(timer)
+  }
+}
+
+// How long the snake flashes red after it dies.
+pub struct DeathTimer(pub Timer);
+
+impl Default for DeathTimer {
+  fn default() -> Self {
+    Self(Timer::from_seconds(DEATH_ANIMATION_SECONDS, false))
+  }
+}
+
+pub struct MovementTimer(pub Timer);
+
+impl Default for MovementTimer {
+  fn default() -> Self {
+    Self(Timer::from_seconds(MOVEMENT_INTERVAL, true))
+  }
+}
+
+// The movement interval before boost and slow-mo. Growth, the difficulty and
+// the start of a run change this, and `movement_interval` works the timer's
+// duration out from it, so the modifiers never pile up on each other.
+pub struct BaseInterval(pub Duration);
+
+impl Default for BaseInterval {
+  fn default() -> Self {
+    Self(Duration::from_secs_f32(MOVEMENT_INTERVAL))
+  }
+}
+
+// Holding the boost key doubles the speed, at the cost of a tail segment every
+// `BoostDrain` tick. It lets go by itself at the minimum length. Only player
+// one can boost, and since every snake moves on the one `MovementTimer`, a
+// boost speeds up the whole board, player two and the AI included.
+pub struct BoostKey(pub KeyCode);
+
+impl Default for BoostKey {
+  fn default() -> Self {
+    Self(KeyCode::LShift)
+  }
+}
+
+#[derive(Default)]
+pub struct Boosting(pub bool);
+
+pub struct BoostDrain(pub Timer);
+
+impl Default for BoostDrain {
+  fn default() -> Self {
+    Self(Timer::from_seconds(BOOST_DRAIN_INTERVAL, true))
+  }
+}
+
+// Every growth multiplies the movement interval by `factor`, down to `floor`.
+pub struct SpeedConfig {
+  pub initial_interval: Duration,
+  pub factor: f32,
+  pub floor: Duration,
+}
+
+impl Default for SpeedConfig {
+  fn default() -> Self {
+    Self {
+      initial_interval: Duration::from_secs_f32(MOVEMENT_INTERVAL),
+      factor: 0.97,
+      floor: Duration::from_millis(60),
+    }
+  }
+}
+
+// `amount` is the number of segments to add.
+pub struct GrowthEvent {
+  pub player: Player,
+  pub amount: u32,
+}
+pub struct GameOverEvent;
+
+pub struct GameWonEvent;
+
+// A death that still leaves the player with lives to spare.
+pub struct LifeLostEvent;
+
+// A player's snake running into something. Outside of versus any crash is
+// game over, see `snake_crashes`.
+pub struct SnakeCrashed {
+  pub player: Player,
+}
+
+pub struct ShrinkEvent {
+  pub player: Player,
+}
+
+// Sent for every food eaten, alongside the `GrowthEvent` or `ShrinkEvent` it
+// causes, so scoring and the cosmetic systems don't need to repeat the
+// collision checks. `delta` is the base points before any combo, and negative
+// for poison.
+pub struct ScoreEvent {
+  pub player: Player,
+  pub delta: i32,
+  pub position: Position,
+  pub kind: FoodKind,
+}
+
+impl ScoreEvent {
+  // The combo multiplier only ever applies to gains.
+  pub fn apply(&self, score: u32, multiplier: u32) -> u32 {
+    if self.delta > 0 {
+      score + self.delta as u32 * multiplier
+    } else {
+      score.saturating_sub(self.delta.unsigned_abs())
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoodKind {
+  Normal,
+  Bonus,
+  Poison,
+  Big,
+}
+
+impl FoodKind {
+  pub fn delta(&self) -> i32 {
+    match self {
+      Self::Normal => FOOD_POINTS as i32,
+      Self::Bonus => BONUS_FOOD_POINTS as i32,
+      Self::Poison => -(POISON_PENALTY as i32),
+      Self::Big => BIG_FOOD_POINTS as i32,
+    }
+  }
+}
+
+// Sent when a snake turns because of its input. Holding a key down or pressing
+// the way it's already going doesn't count, and neither does bouncing.
+pub struct DirectionChanged {
+  pub from: Direction,
+  pub to: Direction,
+}
+
+// Anything that food can't be spawned on top of.
+pub type Occupying = Or<(
+  With<Food>,
+  With<PoisonFood>,
+  With<SnakeSegment>,
+  With<Wall>,
+  With<Portal>,
+  With<BigFoodPart>,
+  With<PowerUp>,
+)>;
+
+pub const INPUT_QUEUE_LENGTH: usize = 2;
+pub const MOVEMENT_INTERVAL: f32 = 0.15;
+pub const FOOD_POINTS: u32 = 1;
+pub const BONUS_FOOD_POINTS: u32 = 5;
+pub const BONUS_FOOD_LIFETIME: f32 = 5.0;
+pub const BIG_FOOD_POINTS: u32 = 10;
+pub const POISON_PENALTY: u32 = 1;
+pub const BIG_FOOD_GROWTH: u32 = 3;
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+pub const BOOST_DRAIN_INTERVAL: f32 = 0.5;
+pub const RESPAWN_INVINCIBILITY: f32 = 2.0;
+pub const GRADIENT_HUE_SHIFT: f32 = 60.0;
+pub const SIMULATION_STEP: f64 = 1.0 / 60.0;
+pub const MAX_CATCH_UP_STEPS: u32 = 10;
+pub const SPAWN_INTERVAL: f32 = 3.0;
+pub const DEATH_ANIMATION_SECONDS: f32 = 0.6;
+pub const DEATH_FLASH_INTERVAL: f32 = 0.1;
+
+pub fn setup(
+  mut commands: Commands,
+  config: Res<MaterialsConfig>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+  commands.spawn_bundle(OrthographicCameraBundle::new_2d()).insert(MainCamera);
+  commands.insert_resource(Materials {
+    head_material: materials.add(config.head.into()),
+    segment_material: materials.add(config.segment.into()),
+    food_material: materials.add(config.food.into()),
+    wall_material: materials.add(config.wall.into()),
+    ai_head_material: materials.add(config.ai_head.into()),
+    ai_segment_material: materials.add(config.ai_segment.into()),
+    player_two_head_material: materials.add(config.player_two_head.into()),
+    player_two_segment_material: materials.add(config.player_two_segment.into()),
+    poison_material: materials.add(config.poison.into()),
+    bonus_material: materials.add(config.bonus.into()),
+    grid_material: materials.add(config.grid.into()),
+    portal_material: materials.add(config.portal.into()),
+    big_food_material: materials.add(config.big_food.into()),
+    magnet_material: materials.add(config.magnet.into()),
+    ghost_material: materials.add(config.ghost.into()),
+    death_material: materials.add(config.death.into()),
+    border_material: materials.add(config.border.into()),
+    rotten_material: materials.add(config.rotten.into()),
+    slow_mo_material: materials.add(config.slow_mo.into()),
+    assist_material: materials.add(config.assist.into()),
+  });
+}
+
+pub fn spawn_segment(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  position: Position,
+  visual: &VisualConfig,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(SnakeSegment)
+    .insert(position)
+    .insert(PrevPosition::new(position))
+    .insert(Size::square(visual.segment_scale))
+    .id()
+}
+
+pub fn spawn_wall(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  position: Position,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(Wall)
+    .insert(position)
+    .insert(Size::square(1.0))
+    .id()
+}
+
+pub fn spawn_walls(mut commands: Commands, materials: Res<Materials>, level: Res<LevelData>) {
+  for &position in level.walls.iter() {
+    spawn_wall(&mut commands, materials.wall_material.clone(), position);
+  }
+}
+
+pub fn spawn_portal(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  id: u8,
+  position: Position,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(Portal { id })
+    .insert(position)
+    .insert(Size::square(0.9))
+    .id()
+}
+
+pub fn spawn_portals(mut commands: Commands, materials: Res<Materials>, level: Res<LevelData>) {
+  for &(id, position) in level.portals.iter() {
+    spawn_portal(&mut commands, materials.portal_material.clone(), id, position);
+  }
+}
+
+pub fn player_materials(
+  materials: &Materials,
+  player: Player,
+) -> (Handle<ColorMaterial>, Handle<ColorMaterial>) {
+  match player {
+    Player::One => (materials.head_material.clone(), materials.segment_material.clone()),
+    Player::Two => (
+      materials.player_two_head_material.clone(),
+      materials.player_two_segment_material.clone(),
+    ),
+  }
+}
+
+// Spawns a snake over `positions`, head first, and returns its segments in the
+// same order.
+pub fn spawn_player_snake(
+  commands: &mut Commands,
+  materials: &Materials,
+  player: Player,
+  positions: &[Position],
+  direction: Direction,
+  visual: &VisualConfig,
+) -> Vec<Entity> {
+  let (head_material, segment_material) = player_materials(materials, player);
+  let head = commands
+    .spawn_bundle(SpriteBundle {
+      material: head_material,
+      sprite: Sprite::new(Vec2::new(10.0, 10.0)),
+      ..Default::default()
+    })
+    .insert(SnakeHead::new(direction))
+    .insert(SnakeSegment)
+    .insert(player)
+    .insert(positions[0])
+    .insert(PrevPosition::new(positions[0]))
+    .insert(Size::square(visual.head_scale))
+    .id();
+  let mut segments = vec![head];
+  for &position in positions.iter().skip(1) {
+    segments.push(spawn_segment(commands, segment_material.clone(), position, visual));
+  }
+  segments
+}
+
+// Where each player's snake starts. Player two starts mirrored across the
+// arena from player one. A spawn that doesn't fit falls back to the default
+// snake on the level's start tile.
+pub fn player_spawns(
+  level: &LevelData,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+  spawn: &SnakeSpawnConfig,
+  two_player: bool,
+) -> Vec<(Player, SnakeSpawnConfig)> {
+  let mut spawns = vec![(Player::One, *spawn)];
+  if two_player {
+    spawns.push((Player::Two, spawn.mirrored(&arena)));
+  }
+  spawns
+    .into_iter()
+    .map(|(player, spawn)| {
+      let mut spawn = spawn.facing_safely(arena, edges, &level.walls);
+      if !spawn.fits(&arena, &level.walls) {
+        warn!("Snake spawn for {:?} doesn't fit on the board, using the default", player);
+        spawn = SnakeSpawnConfig { start: level.start, ..Default::default() };
+        if player == Player::Two {
+          spawn = spawn.mirrored(arena);
+        }
+        spawn = spawn.facing_safely(arena, edges, &level.walls);
+      }
+      (player, spawn)
+    })
+    .collect()
+}
+
+fn spawn_players(
+  commands: &mut Commands,
+  segments: &mut SnakeSegments,
+  materials: &Materials,
+  level: &LevelData,
+  arena: &ArenaSize,
+  edges: &EdgeConfig,
+  spawn: &SnakeSpawnConfig,
+  two_player: bool,
+  visual: &VisualConfig,
+) {
+  segments.0.clear();
+  for (player, spawn) in player_spawns(level, arena, edges, spawn, two_player) {
+    let positions = spawn.positions();
+    let direction = spawn.direction;
+    let snake = spawn_player_snake(commands, materials, player, &positions, direction, visual);
+    segments.0.insert(player, snake);
+  }
+}
+
+pub fn spawn_snake(
+  mut commands: Commands,
+  mut segments: ResMut<SnakeSegments>,
+  materials: Res<Materials>,
+  level: Res<LevelData>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  spawn: Res<SnakeSpawnConfig>,
+  play_mode: Res<PlayMode>,
+  visual: Res<VisualConfig>,
+) {
+  spawn_players(
+    &mut commands,
+    &mut segments,
+    &materials,
+    &level,
+    &arena,
+    &edges,
+    &spawn,
+    play_mode.two_players(),
+    &visual,
+  );
+}
+
+// Losing a life, or the end of a round in versus, puts the players' snakes
+// back at the start, invincible for a moment so they can get their bearings.
+pub fn respawn_snake(
+  mut commands: Commands,
+  mut life_lost_reader: EventReader<LifeLostEvent>,
+  mut round_reader: EventReader<RoundOver>,
+  mut segments: ResMut<SnakeSegments>,
+  mut last_tail_position: ResMut<LastTailPosition>,
+  mut invincible: ResMut<Invincible>,
+  materials: Res<Materials>,
+  level: Res<LevelData>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  spawn: Res<SnakeSpawnConfig>,
+  play_mode: Res<PlayMode>,
+  visual: Res<VisualConfig>,
+) {
+  let life_lost = life_lost_reader.iter().last().is_some();
+  let round_over = round_reader.iter().last().is_some();
+  if !life_lost && !round_over { return; }
+  for &entity in segments.0.values().flatten() {
+    commands.entity(entity).despawn();
+  }
+  last_tail_position.0.clear();
+  spawn_players(
+    &mut commands,
+    &mut segments,
+    &materials,
+    &level,
+    &arena,
+    &edges,
+    &spawn,
+    play_mode.two_players(),
+    &visual,
+  );
+  invincible.0 = Timer::from_seconds(RESPAWN_INVINCIBILITY, false);
+}
+
+pub fn snake_movement_input(
+  input: Res<Input<KeyCode>>,
+  bindings: Res<KeyBindings>,
+  alternate_bindings: Res<AlternateKeyBindings>,
+  control_scheme: Res<ControlScheme>,
+  play_mode: Res<PlayMode>,
+  replay_mode: Res<ReplayMode>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+) {
+  for (mut head, player) in heads.iter_mut() {
+    // A replay steers player one by itself.
+    if *player == Player::One && *replay_mode == ReplayMode::Playback { continue; }
+    // The primary bindings are checked first, so when conflicting keys are
+    // pressed on the same frame the primary one is queued and the alternate
+    // one is rejected as a reversal.
+    let mut keys = Vec::new();
+    if *player == Player::One {
+      keys.extend_from_slice(&bindings.keys());
+    }
+    if *player == Player::Two || !play_mode.two_players() {
+      if let Some(alternate_bindings) = &alternate_bindings.0 {
+        keys.extend_from_slice(&alternate_bindings.keys());
+      }
+    }
+    for (key, direction) in keys {
+      if !input.just_pressed(key) { continue; }
+      let direction = match *control_scheme {
+        ControlScheme::Absolute => direction,
+        ControlScheme::Relative => match direction {
+          Direction::Left => head.queued_direction().turn_left(),
+          Direction::Right => head.queued_direction().turn_right(),
+          _ => continue,
+        },
+      };
+      head.queue_direction(direction);
+    }
+  }
+}
+
+// For inputs other than the keyboard, which only ever steer player one.
+pub fn steer_player_one(heads: &mut Query<(&mut SnakeHead, &Player)>, direction: Direction) {
+  for (mut head, player) in heads.iter_mut() {
+    if *player == Player::One {
+      head.queue_direction(direction);
+    }
+  }
+}
+
+// While this is running the players' snakes pass through bodies, their own
+// included. It starts out finished.
+pub struct Invincible(pub Timer);
+
+impl Default for Invincible {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(0.0, false);
+    timer.tick(Duration::default());
+    Self(timer)
+  }
+}
+
+// Everything `move_snake` needs to know about the board besides the snakes.
+pub struct MovementRules {
+  pub arena: ArenaSize,
+  pub edges: EdgeConfig,
+  pub walls: Vec<Position>,
+  pub portals: Vec<(u8, Position)>,
+  pub fatal_blocked_portals: bool,
+}
+
+pub struct SnakeStep {
+  // Head first, as many as went in.
+  pub positions: Vec<Position>,
+  // Where the snake is heading now, which only changes when it bounces.
+  pub direction: Direction,
+  pub game_over: bool,
+  // The tile a segment grown this tick goes on.
+  pub grow_at: Position,
+}
+
+// Moves one snake a tile in `direction`. `segments` is head first and has at
+// least the head in it. `bodies` is every snake tile that's still taken after
+// this tick, which leaves out the tails since they move out of the way. Running
+// into a body (unless it's a `ghost`) or a wall, or past an edge that's
+// `WallBehavior::Die`, ends the game, but the snake is moved either way.
+pub fn move_snake(
+  segments: &[Position],
+  direction: Direction,
+  bodies: &[Position],
+  ghost: bool,
+  rules: &MovementRules,
+) -> SnakeStep {
+  let mut game_over = false;
+  let next = segments[0] + direction;
+  let mut head = match rules.edges.crossed(next, &rules.arena) {
+    None => next,
+    Some(WallBehavior::Die) => {
+      game_over = true;
+      next
+    }
+    Some(WallBehavior::Wrap) => next.wrapped(&rules.arena),
+    Some(WallBehavior::Bounce) => {
+      // The body is reversed in place, so the tail end leads and the snake
+      // goes on the way its tail was going rather than back into its neck.
+      let positions = segments.iter().rev().copied().collect::<Vec<Position>>();
+      let tail_direction = match segments.len() {
+        0 | 1 => None,
+        len => Direction::between(segments[len - 2], segments[len - 1]),
+      };
+      return SnakeStep {
+        direction: tail_direction.unwrap_or_else(|| direction.opposite()),
+        game_over: false,
+        grow_at: *positions.last().unwrap(),
+        positions,
+      };
+    }
+  };
+  let portal = rules.portals.iter().find(|&&(_, position)| position == head);
+  if let Some(&(id, portal_position)) = portal {
+    let partner = rules.portals
+      .iter()
+      .find(|&&(other, position)| other == id && position != portal_position)
+      .map(|&(_, position)| position);
+    match partner {
+      Some(partner) if bodies.contains(&partner) => game_over |= rules.fatal_blocked_portals,
+      Some(partner) => head = partner,
+      None => {}
+    }
+  }
+  if (!ghost && bodies.contains(&head)) || rules.walls.contains(&head) {
+    game_over = true;
+  }
+  let mut positions = vec![head];
+  positions.extend_from_slice(&segments[..segments.len() - 1]);
+  SnakeStep {
+    positions,
+    direction,
+    game_over,
+    grow_at: *segments.last().unwrap(),
+  }
+}
+
+pub fn snake_movement(
+  mut timer: ResMut<MovementTimer>,
+  arena: Res<ArenaSize>,
+  edges: Res<EdgeConfig>,
+  segments: Res<SnakeSegments>,
+  mut last_tail_position: ResMut<LastTailPosition>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+  mut positions: Query<&mut Position, With<SnakeSegment>>,
+  walls: Query<&Position, (With<Wall>, Without<SnakeSegment>)>,
+  portals: Query<(&Portal, &Position), Without<SnakeSegment>>,
+  fatal_blocked_portals: Res<FatalBlockedPortals>,
+  countdown: Res<Countdown>,
+  invincible: Res<Invincible>,
+  mut stats: ResMut<GameStats>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+  mut direction_writer: EventWriter<DirectionChanged>,
+) {
+  if !countdown.0.finished() { return; }
+  stats.steps_simulated += 1;
+  if !timer.0.tick(Duration::from_secs_f64(SIMULATION_STEP)).just_finished() { return; }
+  stats.ticks_survived += 1;
+  // Every snake's body before anyone moves, including the AI opponent's. The
+  // players' tails move out of the way this tick, so turning into one is safe.
+  let all_positions = positions.iter_mut().map(|position| *position).collect::<Vec<Position>>();
+  let mut body_positions = all_positions.clone();
+  for tail in segments.0.values().filter_map(|player_segments| player_segments.last()) {
+    let tail_position = match positions.get_mut(*tail) {
+      Ok(position) => *position,
+      Err(_) => continue,
+    };
+    if let Some(index) = body_positions.iter().position(|&position| position == tail_position) {
+      body_positions.swap_remove(index);
+    }
+  }
+  let rules = MovementRules {
+    arena: *arena,
+    edges: *edges,
+    walls: walls.iter().copied().collect(),
+    portals: portals.iter().map(|(portal, &position)| (portal.id, position)).collect(),
+    fatal_blocked_portals: fatal_blocked_portals.0,
+  };
+  let mut new_head_positions = Vec::new();
+  for (mut head, player) in heads.iter_mut() {
+    let player_segments = match segments.0.get(player) {
+      Some(player_segments) => player_segments,
+      None => continue,
+    };
+    // A snake whose segments haven't all been spawned yet, like right after a
+    // load, sits this tick out.
+    let segment_positions = player_segments
+      .iter()
+      .map(|&entity| positions.get_mut(entity).ok().map(|position| *position))
+      .collect::<Option<Vec<Position>>>();
+    let segment_positions = match segment_positions {
+      Some(segment_positions) if !segment_positions.is_empty() => segment_positions,
+      _ => continue,
+    };
+    if let Some(direction) = head.input_queue.pop_front() {
+      head.input_direction = direction;
+    }
+    if head.input_direction != head.movement_direction {
+      let (from, to) = (head.movement_direction, head.input_direction);
+      direction_writer.send(DirectionChanged { from, to });
+    }
+    // A head still inside a body when the ghost wears off stays a ghost until
+    // it's out again.
+    let head_overlaps = all_positions
+      .iter()
+      .filter(|&&position| position == segment_positions[0])
+      .count() > 1;
+    let ghost = !invincible.0.finished() || head_overlaps;
+    let step = move_snake(&segment_positions, head.input_direction, &body_positions, ghost, &rules);
+    if step.direction != head.input_direction {
+      head.input_queue.clear();
+    }
+    head.input_direction = step.direction;
+    head.movement_direction = step.direction;
+    if step.game_over {
+      crash_writer.send(SnakeCrashed { player: *player });
+    }
+    // Two heads landing on the same tile is a head-on collision, which both
+    // snakes crash in.
+    let head_on = new_head_positions.iter().find(|&&(_, position)| position == step.positions[0]);
+    if let Some(&(other, _)) = head_on {
+      crash_writer.send(SnakeCrashed { player: other });
+      crash_writer.send(SnakeCrashed { player: *player });
+    }
+    new_head_positions.push((*player, step.positions[0]));
+    step.positions
+      .iter()
+      .zip(player_segments.iter())
+      .for_each(|(&position, &segment)| {
+        if let Ok(mut segment_position) = positions.get_mut(segment) {
+          *segment_position = position;
+        }
+      });
+    last_tail_position.0.insert(*player, step.grow_at);
+  }
+}
+
+pub fn snake_eating(
+  mut commands: Commands,
+  mut growth_writer: EventWriter<GrowthEvent>,
+  mut shrink_writer: EventWriter<ShrinkEvent>,
+  mut score_writer: EventWriter<ScoreEvent>,
+  food_positions: Query<(&Position, Entity, Option<&BonusFood>, Option<&GrowthAmount>), With<Food>>,
+  poison_positions: Query<(&Position, Entity), With<PoisonFood>>,
+  big_food_parts: Query<(&Position, Entity, &BigFoodPart)>,
+  mut big_food: Query<&mut BigFood>,
+  head_positions: Query<(&Position, &Player), With<SnakeHead>>,
+) {
+  for (head_position, player) in head_positions.iter() {
+    for (food_position, food_entity, bonus, growth) in food_positions.iter() {
+      if food_position == head_position {
+        commands.entity(food_entity).despawn();
+        let amount = growth.map_or(1, |growth| growth.0);
+        growth_writer.send(GrowthEvent { player: *player, amount });
+        let kind = if bonus.is_some() { FoodKind::Bonus } else { FoodKind::Normal };
+        score_writer.send(ScoreEvent {
+          player: *player,
+          delta: kind.delta(),
+          position: *food_position,
+          kind,
+        });
+      }
+    }
+    for (poison_position, poison_entity) in poison_positions.iter() {
+      if poison_position == head_position {
+        commands.entity(poison_entity).despawn();
+        shrink_writer.send(ShrinkEvent { player: *player });
+        score_writer.send(ScoreEvent {
+          player: *player,
+          delta: FoodKind::Poison.delta(),
+          position: *poison_position,
+          kind: FoodKind::Poison,
+        });
+      }
+    }
+    for (part_position, part_entity, part) in big_food_parts.iter() {
+      if part_position != head_position { continue; }
+      commands.entity(part_entity).despawn();
+      let mut food = match big_food.get_mut(part.0) {
+        Ok(food) => food,
+        Err(_) => continue,
+      };
+      food.bites_remaining = food.bites_remaining.saturating_sub(1);
+      if food.bites_remaining > 0 { continue; }
+      commands.entity(part.0).despawn();
+      growth_writer.send(GrowthEvent { player: *player, amount: BIG_FOOD_GROWTH });
+      score_writer.send(ScoreEvent {
+        player: *player,
+        delta: FoodKind::Big.delta(),
+        position: *part_position,
+        kind: FoodKind::Big,
+      });
+    }
+  }
+}
+
+pub fn snake_growth(
+  mut commands: Commands,
+  last_tail_position: Res<LastTailPosition>,
+  mut segments: ResMut<SnakeSegments>,
+  mut growth_reader: EventReader<GrowthEvent>,
+  materials: Res<Materials>,
+  visual: Res<VisualConfig>,
+  positions: Query<&Position, With<SnakeSegment>>,
+) {
+  for event in growth_reader.iter() {
+    let material = match event.player {
+      Player::One => materials.segment_material.clone(),
+      Player::Two => materials.player_two_segment_material.clone(),
+    };
+    // Before the first movement tick there's no last tail position yet, so the
+    // new segments go on the current tail instead.
+    let tail_position = last_tail_position.0.get(&event.player).copied().or_else(|| {
+      segments.0
+        .get(&event.player)?
+        .iter()
+        .rev()
+        .find_map(|&entity| positions.get(entity).ok().copied())
+    });
+    let tail_position = match tail_position {
+      Some(tail_position) => tail_position,
+      None => continue,
+    };
+    // All the new segments start on the same tile and spread out as the snake
+    // moves on.
+    for _ in 0..event.amount {
+      let segment = spawn_segment(&mut commands, material.clone(), tail_position, &visual);
+      segments.0.entry(event.player).or_default().push(segment);
+    }
+  }
+}
+
+// Poison takes the tail off, unless the snake is already as short as it can get.
+pub fn snake_shrink(
+  mut commands: Commands,
+  min_length: Res<MinLength>,
+  mut segments: ResMut<SnakeSegments>,
+  mut shrink_reader: EventReader<ShrinkEvent>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+) {
+  for event in shrink_reader.iter() {
+    let player_segments = match segments.0.get_mut(&event.player) {
+      Some(player_segments) => player_segments,
+      None => continue,
+    };
+    if player_segments.len() <= min_length.0 {
+      crash_writer.send(SnakeCrashed { player: event.player });
+    } else if let Some(tail) = player_segments.pop() {
+      commands.entity(tail).despawn();
+    }
+  }
+}
+
+// Runs every simulation step before `snake_movement`. A replay boosts on the
+// steps the recording did rather than when the key is held.
+pub fn boost(
+  mut commands: Commands,
+  countdown: Res<Countdown>,
+  stats: Res<GameStats>,
+  input: Res<Input<KeyCode>>,
+  boost_key: Res<BoostKey>,
+  replay_mode: Res<ReplayMode>,
+  mut replay: ResMut<Replay>,
+  mut boosting: ResMut<Boosting>,
+  mut drain: ResMut<BoostDrain>,
+  min_length: Res<MinLength>,
+  mut segments: ResMut<SnakeSegments>,
+) {
+  if !countdown.0.finished() { return; }
+  let player_segments = match segments.0.get_mut(&Player::One) {
+    Some(player_segments) => player_segments,
+    None => return,
+  };
+  let held = match *replay_mode {
+    ReplayMode::Playback => replay.boosting_at(stats.steps_simulated),
+    _ => input.pressed(boost_key.0),
+  };
+  let can_boost = player_segments.len() > min_length.0;
+  let wants_boost = held && can_boost;
+  if wants_boost != boosting.0 {
+    boosting.0 = wants_boost;
+    drain.0.reset();
+    if *replay_mode == ReplayMode::Recording {
+      replay.boost_toggles.push(stats.steps_simulated);
+    }
+  }
+  if boosting.0 && drain.0.tick(Duration::from_secs_f64(SIMULATION_STEP)).just_finished() {
+    if let Some(tail) = player_segments.pop() {
+      commands.entity(tail).despawn();
+    }
+  }
+}
+
+// A run always starts at normal speed, `end_run` having reset the timer.
+pub fn reset_boost(mut boosting: ResMut<Boosting>) {
+  boosting.0 = false;
+}
+
+pub fn update_game_stats(
+  segments: Res<SnakeSegments>,
+  score: Res<Score>,
+  mut stats: ResMut<GameStats>,
+) {
+  stats.length = segments.len(Player::One);
+  stats.score = score.0;
+}
+
+pub fn reset_lives(starting_lives: Res<StartingLives>, mut lives: ResMut<Lives>) {
+  lives.0 = starting_lives.0;
+}
+
+pub fn start_countdown(mut countdown: ResMut<Countdown>) {
+  countdown.0.reset();
+}
+
+pub fn spawn_timer(countdown: Res<Countdown>, mut timer: ResMut<SpawnTimer>) {
+  if countdown.0.finished() {
+    timer.0.tick(Duration::from_secs_f64(SIMULATION_STEP));
+  }
+}
+
+pub fn reset_spawn_timer(mut timer: ResMut<SpawnTimer>) {
+  timer.0.reset();
+}
+
+pub fn countdown(time: Res<Time>, mut countdown: ResMut<Countdown>) {
+  countdown.0.tick(time.delta());
+}
+
+pub fn survival_timer(countdown: Res<Countdown>, mut survival_time: ResMut<SurvivalTime>) {
+  if countdown.0.finished() {
+    survival_time.0.tick(Duration::from_secs_f64(SIMULATION_STEP));
+  }
+}
+
+// Running out of time crashes every snake on the step the limit is reached.
+// That ends the run however many lives are left, or draws the round in versus.
+pub fn time_attack(
+  survival_time: Res<SurvivalTime>,
+  time_attack: Option<Res<TimeAttack>>,
+  play_mode: Res<PlayMode>,
+  segments: Res<SnakeSegments>,
+  mut lives: ResMut<Lives>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+) {
+  let time_attack = match time_attack {
+    Some(time_attack) => time_attack,
+    None => return,
+  };
+  let elapsed = survival_time.0.elapsed();
+  let step = Duration::from_secs_f64(SIMULATION_STEP);
+  if elapsed < time_attack.limit || elapsed.saturating_sub(step) >= time_attack.limit { return; }
+  if *play_mode != PlayMode::Versus {
+    lives.0 = 0;
+  }
+  for (player, _) in segments.iter() {
+    crash_writer.send(SnakeCrashed { player });
+  }
+}
+
+// Anything but poison counts as a meal. Hunger doesn't run down while the
+// countdown is, and `Paused` stops it along with the rest of `Playing`.
+pub fn hunger(
+  countdown: Res<Countdown>,
+  penalty: Res<HungerPenalty>,
+  hunger: Option<ResMut<Hunger>>,
+  mut score_reader: EventReader<ScoreEvent>,
+  mut life_lost_reader: EventReader<LifeLostEvent>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+  mut shrink_writer: EventWriter<ShrinkEvent>,
+) {
+  let mut hunger = match hunger {
+    Some(hunger) => hunger,
+    None => return,
+  };
+  let ate = score_reader.iter().any(|event| event.kind != FoodKind::Poison);
+  if ate || life_lost_reader.iter().next().is_some() {
+    hunger.0.reset();
+  }
+  let step = Duration::from_secs_f64(SIMULATION_STEP);
+  if !countdown.0.finished() || !hunger.0.tick(step).just_finished() { return; }
+  match *penalty {
+    HungerPenalty::Die => crash_writer.send(SnakeCrashed { player: Player::One }),
+    HungerPenalty::Shrink => {
+      shrink_writer.send(ShrinkEvent { player: Player::One });
+      hunger.0.reset();
+    }
+  }
+}
+
+pub fn reset_hunger(hunger: Option<ResMut<Hunger>>) {
+  if let Some(mut hunger) = hunger {
+    hunger.0.reset();
+  }
+}
+
+// Runs after `combo_scoring`, so food that continues a combo is scored with
+// the multiplier it just reached.
+pub fn scoring(
+  combo: Res<Combo>,
+  mut score: ResMut<Score>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  for event in score_reader.iter() {
+    score.0 = event.apply(score.0, combo.multiplier);
+  }
+}
+
+// Only the base interval speeds up; boost and slow-mo go on top of it.
+pub fn increase_speed(
+  speed_config: Res<SpeedConfig>,
+  mut base_interval: ResMut<BaseInterval>,
+  mut growth_reader: EventReader<GrowthEvent>,
+) {
+  for event in growth_reader.iter() {
+    let factor = speed_config.factor.powi(event.amount as i32);
+    base_interval.0 = base_interval.0.mul_f32(factor).max(speed_config.floor);
+  }
+}
+
+// Runs every simulation step before `snake_movement`. Boost halves the base
+// interval and slow-mo stretches it, so letting go of either puts back exactly
+// what was there, even at the speed floor.
+pub fn movement_interval(
+  base_interval: Res<BaseInterval>,
+  boosting: Res<Boosting>,
+  slow_mo: Res<SlowMo>,
+  mut timer: ResMut<MovementTimer>,
+) {
+  let mut interval = base_interval.0;
+  if boosting.0 {
+    interval /= 2;
+  }
+  if !slow_mo.0.finished() {
+    interval *= SLOW_MO_FACTOR;
+  }
+  if timer.0.duration() != interval {
+    timer.0.set_duration(interval);
+  }
+}
+
+// Shared by every way a run can end. `Score` adds up both players' points in
+// versus, so a match never counts towards the high score.
+fn end_run(
+  state: &mut State<GameState>,
+  end_state: GameState,
+  play_mode: &PlayMode,
+  score: &Score,
+  high_score: &mut HighScore,
+  speed_config: &SpeedConfig,
+  base_interval: &mut BaseInterval,
+  timer: &mut MovementTimer,
+  stats: &mut GameStats,
+  survival_time: &mut SurvivalTime,
+) {
+  *stats = GameStats::default();
+  survival_time.0.reset();
+  base_interval.0 = speed_config.initial_interval;
+  timer.0.set_duration(speed_config.initial_interval);
+  timer.0.reset();
+  if *play_mode != PlayMode::Versus && score.0 > high_score.0 {
+    high_score.0 = score.0;
+    if let Err(error) = save_high_score(&high_score_path(), high_score.0) {
+      warn!("Couldn't save high score: {}", error);
+    }
+  }
+  // `replace` so a run ending from `Dying` doesn't leave `Playing` underneath.
+  let _ = state.replace(end_state);
+}
+
+// In versus a crash decides the round instead, see `versus_rounds`.
+pub fn snake_crashes(
+  play_mode: Res<PlayMode>,
+  mut crash_reader: EventReader<SnakeCrashed>,
+  mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+  if *play_mode == PlayMode::Versus { return; }
+  if crash_reader.iter().last().is_some() {
+    game_over_writer.send(GameOverEvent);
+  }
+}
+
+pub fn game_over(
+  mut reader: EventReader<GameOverEvent>,
+  mut state: ResMut<State<GameState>>,
+  mut death_timer: ResMut<DeathTimer>,
+) {
+  // Everything that went wrong this frame only costs the one life.
+  if reader.iter().last().is_none() { return; }
+  if state.push(GameState::Dying).is_ok() {
+    death_timer.0.reset();
+  }
+}
+
+pub fn start_dying(
+  materials: Res<Materials>,
+  segments: Res<SnakeSegments>,
+  mut handles: Query<&mut Handle<ColorMaterial>, With<SnakeSegment>>,
+) {
+  for &entity in segments.0.values().flatten() {
+    if let Ok(mut handle) = handles.get_mut(entity) {
+      *handle = materials.death_material.clone();
+    }
+  }
+}
+
+// Flashes the dead snake until the timer runs out or any key is pressed, then
+// either takes a life and respawns or ends the run.
+pub fn dying(
+  time: Res<Time>,
+  input: Res<Input<KeyCode>>,
+  mut death_timer: ResMut<DeathTimer>,
+  segments: Res<SnakeSegments>,
+  mut visibles: Query<&mut Visible, With<SnakeSegment>>,
+  mut lives: ResMut<Lives>,
+  mut life_lost_writer: EventWriter<LifeLostEvent>,
+  mut state: ResMut<State<GameState>>,
+  play_mode: Res<PlayMode>,
+  score: Res<Score>,
+  mut high_score: ResMut<HighScore>,
+  speed_config: Res<SpeedConfig>,
+  mut base_interval: ResMut<BaseInterval>,
+  mut timer: ResMut<MovementTimer>,
+  mut stats: ResMut<GameStats>,
+  mut survival_time: ResMut<SurvivalTime>,
+) {
+  death_timer.0.tick(time.delta());
+  let done = death_timer.0.finished() || input.get_just_pressed().next().is_some();
+  let flashes = (death_timer.0.elapsed_secs() / DEATH_FLASH_INTERVAL) as u32;
+  let is_visible = done || flashes % 2 == 0;
+  for &entity in segments.0.values().flatten() {
+    if let Ok(mut visible) = visibles.get_mut(entity) {
+      visible.is_visible = is_visible;
+    }
+  }
+  if !done { return; }
+
+  if lives.0 > 1 {
+    lives.0 -= 1;
+    life_lost_writer.send(LifeLostEvent);
+    let _ = state.pop();
+  } else {
+    lives.0 = 0;
+    end_run(
+      &mut state,
+      GameState::GameOver,
+      &play_mode,
+      &score,
+      &mut high_score,
+      &speed_config,
+      &mut base_interval,
+      &mut timer,
+      &mut stats,
+      &mut survival_time,
+    );
+  }
+}
+
+pub fn game_won(
+  mut reader: EventReader<GameWonEvent>,
+  mut state: ResMut<State<GameState>>,
+  play_mode: Res<PlayMode>,
+  score: Res<Score>,
+  mut high_score: ResMut<HighScore>,
+  speed_config: Res<SpeedConfig>,
+  mut base_interval: ResMut<BaseInterval>,
+  mut timer: ResMut<MovementTimer>,
+  mut stats: ResMut<GameStats>,
+  mut survival_time: ResMut<SurvivalTime>,
+) {
+  if reader.iter().next().is_some() {
+    end_run(
+      &mut state,
+      GameState::Won,
+      &play_mode,
+      &score,
+      &mut high_score,
+      &speed_config,
+      &mut base_interval,
+      &mut timer,
+      &mut stats,
+      &mut survival_time,
+    );
+  }
+}
+
+pub fn start_game(input: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+  match state.current() {
+    GameState::Menu | GameState::GameOver | GameState::Won
+      if input.just_pressed(KeyCode::Return) =>
+    {
+      let _ = state.set(GameState::Playing);
+    }
+    _ => {}
+  }
+}
+
+// Space only toggles on `just_pressed`, so holding it down doesn't flicker
+// between the two states every frame.
+pub fn toggle_pause(input: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+  if !input.just_pressed(KeyCode::Space) { return; }
+  match state.current() {
+    GameState::Playing => { let _ = state.push(GameState::Paused); }
+    GameState::Paused => { let _ = state.pop(); }
+    _ => {}
+  }
+}
+
+// Whether the game pauses itself when the window loses focus.
+pub struct AutoPause(pub bool);
+
+impl Default for AutoPause {
+  fn default() -> Self {
+    Self(true)
+  }
+}
+
+// Regaining focus doesn't resume on its own, the pause key still has to be
+// pressed so the snake never starts moving by surprise.
+pub fn auto_pause(
+  auto_pause: Res<AutoPause>,
+  mut focused_reader: EventReader<WindowFocused>,
+  mut state: ResMut<State<GameState>>,
+) {
+  let lost_focus = focused_reader.iter().any(|event| !event.focused);
+  if auto_pause.0 && lost_focus && *state.current() == GameState::Playing {
+    let _ = state.push(GameState::Paused);
+  }
+}
+
+// Walls and portals are rebuilt from the level for every run too, so nothing
+// from the last one is left behind.
+pub fn clear_board(
+  mut commands: Commands,
+  mut score: ResMut<Score>,
+  food: Query<
+    Entity,
+    Or<(With<Food>, With<PoisonFood>, With<BigFood>, With<BigFoodPart>, With<PowerUp>)>,
+  >,
+  segments: Query<Entity, With<SnakeSegment>>,
+  level_tiles: Query<Entity, Or<(With<Wall>, With<Portal>)>>,
+) {
+  for entity in food.iter().chain(segments.iter()).chain(level_tiles.iter()) {
+    commands.entity(entity).despawn();
+  }
+  score.0 = 0;
+}
+
+pub fn exit_on_escape(input: Res<Input<KeyCode>>, mut exit_writer: EventWriter<AppExit>) {
+  if input.just_pressed(KeyCode::Escape) {
+    exit_writer.send(AppExit);
+  }
+}
+
+// Time owed to the simulation that hasn't been stepped through yet. Only time
+// spent in `Playing` counts, so pausing doesn't build up a burst of steps.
+#[derive(Default)]
+pub struct SimulationClock {
+  accumulator: f64,
+  looping: bool,
+}
+
+impl SimulationClock {
+  // Owes the simulation `steps` more steps on top of the time that passes, to
+  // drive it by hand, e.g. from a test that keeps `Time` from moving.
+  pub fn advance(&mut self, steps: u32) {
+    self.owe(SIMULATION_STEP * steps as f64);
+  }
+
+  // After a long hitch, like the window being dragged around, the simulation
+  // drops what it can't catch up on in `MAX_CATCH_UP_STEPS` rather than running
+  // a burst of steps that would make the next frame longer still.
+  fn owe(&mut self, seconds: f64) {
+    let most = MAX_CATCH_UP_STEPS as f64 * SIMULATION_STEP;
+    self.accumulator = (self.accumulator + seconds).min(most);
+  }
+}
+
+// Run criteria for the simulation: movement, eating and growth run once for
+// every `SIMULATION_STEP` that has passed, however many frames that took (but
+// no more than `MAX_CATCH_UP_STEPS` in one frame), and tick the movement timer
+// by exactly that much each time. A run with the same inputs on the same steps
+// plays out the same at any frame rate.
+//
+// Ordering: the simulation steps all run in `Update`, before anything labelled
+// to come after them there, and the render systems in `PostUpdate` only ever
+// see the result of the last step of the frame. A frame can have no steps at
+// all, in which case rendering just carries on interpolating.
+pub fn run_simulation(
+  time: Res<Time>,
+  state: Res<State<GameState>>,
+  mut clock: ResMut<SimulationClock>,
+) -> ShouldRun {
+  if *state.current() != GameState::Playing {
+    *clock = SimulationClock::default();
+    return ShouldRun::No;
+  }
+  if !clock.looping {
+    clock.owe(time.delta_seconds_f64());
+  }
+  clock.looping = clock.accumulator >= SIMULATION_STEP;
+  if clock.looping {
+    clock.accumulator -= SIMULATION_STEP;
+    ShouldRun::YesAndCheckAgain
+  } else {
+    ShouldRun::No
+  }
+}
+
+// For systems outside the `Update` stage, where `SystemSet::on_update` doesn't
+// work.
+pub fn run_while_playing(state: Res<State<GameState>>) -> ShouldRun {
+  if *state.current() == GameState::Playing { ShouldRun::Yes } else { ShouldRun::No }
+}
+
+// How the board is fitted into the window. `Stretch` fills the whole window,
+// even if that makes the tiles rectangular. `Fit` keeps them square and centers
+// the board, leaving empty margins along the longer side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingMode {
+  Stretch,
+  Fit,
+}
+
+impl Default for ScalingMode {
+  fn default() -> Self {
+    Self::Stretch
+  }
+}
+
+// The size of the board in pixels. It's always centered in the window.
+pub fn board_size(window: &Window, arena: &ArenaSize, scaling: ScalingMode) -> Vec2 {
+  match scaling {
+    ScalingMode::Stretch => Vec2::new(window.width(), window.height()),
+    ScalingMode::Fit => {
+      let tile = (window.width() / arena.width as f32).min(window.height() / arena.height as f32);
+      Vec2::new(tile * arena.width as f32, tile * arena.height as f32)
+    }
+  }
+}
+
+pub fn size_scaling(
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  mut q: Query<(&Size, &mut Sprite)>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  for (sprite_size, mut sprite) in q.iter_mut() {
+    sprite.size = Vec2::new(
+      board.x / (arena.width as f32) * sprite_size.width,
+      board.y / (arena.height as f32) * sprite_size.height,
+    );
+  }
+}
+
+// `board_dimension` is the board's width or height in pixels, as given by
+// `board_size`.
+pub fn convert_dimension(dimension: f32, board_dimension: f32, arena_dimension: f32) -> f32 {
+  let tile_dimension = board_dimension / arena_dimension;
+  dimension * tile_dimension - board_dimension / 2.0 + tile_dimension / 2.0
+}
+
+// The world-space center of the tile at `position` on a board `board` pixels
+// in size.
+pub fn tile_translation(position: &Position, board: Vec2, arena: &ArenaSize) -> Vec3 {
+  Vec3::new(
+    convert_dimension(position.x as f32, board.x, arena.width as f32),
+    convert_dimension(position.y as f32, board.y, arena.height as f32),
+    0.0,
+  )
+}
+
+// Every movement tick, whatever a tile was last seen at becomes where it's
+// moving from. Tiles that didn't move that tick stop sliding. This runs as the
+// last simulation step system, since `just_finished` stays set across frames
+// without a step.
+pub fn track_previous_positions(
+  timer: Res<MovementTimer>,
+  mut q: Query<(&Position, &mut PrevPosition)>,
+) {
+  if !timer.0.just_finished() { return; }
+  for (position, mut prev) in q.iter_mut() {
+    prev.position = prev.seen;
+    prev.seen = *position;
+  }
+}
+
+pub fn position_translation(
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  timer: Res<MovementTimer>,
+  mut q: Query<(&Position, Option<&PrevPosition>, &mut Transform)>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  for (pos, prev, mut transform) in q.iter_mut() {
+    let target = tile_translation(pos, board, &arena);
+    transform.translation = match prev {
+      // Anything further than a neighbouring tile wrapped around the edge, so
+      // it jumps instead of sliding across the whole board.
+      Some(prev) if prev.position.is_adjacent(pos) => {
+        tile_translation(&prev.position, board, &arena).lerp(target, timer.0.percent())
+      }
+      _ => target,
+    };
+  }
+}
+
+// The hue drifts by up to `GRADIENT_HUE_SHIFT` degrees towards the tail. The
+// head keeps its own material.
+pub fn segment_gradient(
+  gradient: Res<GradientSegments>,
+  segments: Res<SnakeSegments>,
+  config: Res<MaterialsConfig>,
+  materials: Res<Materials>,
+  mut gradient_materials: ResMut<GradientMaterials>,
+  mut color_materials: ResMut<Assets<ColorMaterial>>,
+  added: Query<Entity, Added<SnakeSegment>>,
+  mut handles: Query<&mut Handle<ColorMaterial>, (With<SnakeSegment>, Without<SnakeHead>)>,
+) {
+  // Grown segments only show up here the frame after they're added to the list.
+  let changed = gradient.is_changed() || segments.is_changed() || config.is_changed();
+  if !changed && added.iter().next().is_none() { return; }
+  for (player, entities) in segments.0.iter() {
+    let (base_color, base_material) = match player {
+      Player::One => (config.segment, &materials.segment_material),
+      Player::Two => (config.player_two_segment, &materials.player_two_segment_material),
+    };
+    let [hue, saturation, lightness, alpha] = base_color.as_hlsa_f32();
+    for (index, &entity) in entities.iter().enumerate().skip(1) {
+      let mut handle = match handles.get_mut(entity) {
+        Ok(handle) => handle,
+        Err(_) => continue,
+      };
+      if !gradient.0 {
+        *handle = base_material.clone();
+        continue;
+      }
+      let shift = index as f32 / entities.len() as f32 * GRADIENT_HUE_SHIFT;
+      let color = Color::hsla((hue + shift) % 360.0, saturation, lightness, alpha);
+      let material = gradient_materials.0
+        .entry((*player, index))
+        .or_insert_with(|| color_materials.add(color.into()));
+      if let Some(color_material) = color_materials.get_mut(&*material) {
+        color_material.color = color;
+      }
+      *handle = material.clone();
+    }
+  }
+}
+
+// Turns every head to face where it's going. A plain square looks the same
+// either way, but a directional head sprite should be drawn pointing up.
+pub fn head_rotation(mut heads: Query<(&SnakeHead, &mut Transform)>) {
+  for (head, mut transform) in heads.iter_mut() {
+    transform.rotation = Quat::from_rotation_z(head.movement_direction.angle());
+  }
+}
+
+// How a body segment joins its neighbours, so a sprite set can draw the snake
+// as one connected shape. Directions point from the segment to the neighbour
+// on that side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentShape {
+  // Neighbours on opposite sides, pointing towards the head.
+  Straight(Direction),
+  // Neighbours on two sides next to each other: towards the head, then the tail.
+  Corner(Direction, Direction),
+  // The last segment, pointing towards the rest of the body.
+  Tail(Direction),
+}
+
+// Tails are drawn a little smaller than the body, which is all a plain square
+// can show of its shape.
+pub const TAIL_SCALE: f32 = 0.75;
+
+// Which way `to` is from `from` when they're next to each other, counting
+// neighbours across a wrapped edge.
+fn neighbor_direction(from: Position, to: Position, arena: &ArenaSize) -> Option<Direction> {
+  Direction::between(from, to).or_else(|| {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    match (dx, dy) {
+      (dx, 0) if dx == arena.width as i32 - 1 => Some(Direction::Left),
+      (dx, 0) if dx == 1 - arena.width as i32 => Some(Direction::Right),
+      (0, dy) if dy == arena.height as i32 - 1 => Some(Direction::Down),
+      (0, dy) if dy == 1 - arena.height as i32 => Some(Direction::Up),
+      _ => None,
+    }
+  })
+}
+
+fn clockwise(direction: Direction) -> Direction {
+  match direction {
+    Direction::Up => Direction::Right,
+    Direction::Right => Direction::Down,
+    Direction::Down => Direction::Left,
+    Direction::Left => Direction::Up,
+  }
+}
+
+impl SegmentShape {
+  // Straight and tail sprites are drawn pointing up, corners joining the top
+  // and right sides.
+  pub fn angle(&self) -> f32 {
+    match *self {
+      Self::Straight(direction) | Self::Tail(direction) => direction.angle(),
+      Self::Corner(head, tail) if clockwise(head) == tail => head.angle(),
+      Self::Corner(_, tail) => tail.angle(),
+    }
+  }
+}
+
+// Works out every player segment's shape from where its neighbours are. It runs
+// every frame, after the moves and growth of the frame. A segment on the same
+// tile as its neighbour, like one that just grew, keeps its last shape.
+pub fn segment_shapes(
+  mut commands: Commands,
+  segments: Res<SnakeSegments>,
+  arena: Res<ArenaSize>,
+  positions: Query<&Position, With<SnakeSegment>>,
+  mut shapes: Query<
+    (Option<&SegmentShape>, &mut Transform),
+    (With<SnakeSegment>, Without<SnakeHead>),
+  >,
+) {
+  for entities in segments.0.values() {
+    let snake_positions = entities
+      .iter()
+      .map(|&entity| positions.get(entity).ok().copied())
+      .collect::<Option<Vec<Position>>>();
+    let snake_positions = match snake_positions {
+      Some(snake_positions) => snake_positions,
+      None => continue,
+    };
+    for index in 1..entities.len() {
+      let position = snake_positions[index];
+      let towards_head = neighbor_direction(position, snake_positions[index - 1], &arena);
+      let towards_tail = snake_positions
+        .get(index + 1)
+        .map(|&next| neighbor_direction(position, next, &arena));
+      let shape = match (towards_head, towards_tail) {
+        (Some(head), None) => SegmentShape::Tail(head),
+        (Some(head), Some(Some(tail))) if tail == head.opposite() => SegmentShape::Straight(head),
+        (Some(head), Some(Some(tail))) if tail != head => SegmentShape::Corner(head, tail),
+        _ => continue,
+      };
+      let (current, mut transform) = match shapes.get_mut(entities[index]) {
+        Ok(shape) => shape,
+        Err(_) => continue,
+      };
+      if current == Some(&shape) { continue; }
+      commands.entity(entities[index]).insert(shape);
+      transform.rotation = Quat::from_rotation_z(shape.angle());
+      let scale = if let SegmentShape::Tail(_) = shape { TAIL_SCALE } else { 1.0 };
+      transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+  }
+}
+
+// Which tiles are taken, row by row from the bottom left. Marking the taken
+// ones costs a hash lookup each rather than one for every tile on the board,
+// which is what kept a nearly full 100x100 board from stalling.
+fn taken_tiles(arena: &ArenaSize, taken_positions: &HashSet<Position>) -> Vec<bool> {
+  let mut taken = vec![false; (arena.width * arena.height) as usize];
+  for position in taken_positions.iter().filter(|position| position.in_arena(arena)) {
+    taken[(position.y as u32 * arena.width + position.x as u32) as usize] = true;
+  }
+  taken
+}
+
+fn tile_at(index: usize, arena: &ArenaSize) -> Position {
+  let index = index as u32;
+  Position { x: (index % arena.width) as i32, y: (index / arena.width) as i32 }
+}
+
+// The board is won once snakes and walls cover every tile of it. Food, poison
+// and power-ups taking the last free tiles don't count.
+pub fn board_filled<'a>(arena: &ArenaSize, solid: impl Iterator<Item = &'a Position>) -> bool {
+  let covered = solid.filter(|position| position.in_arena(arena)).collect::<HashSet<_>>();
+  covered.len() == (arena.width * arena.height) as usize
+}
+
+// Row by row from the bottom left.
+pub fn free_positions(arena: &ArenaSize, taken_positions: &HashSet<Position>) -> Vec<Position> {
+  taken_tiles(arena, taken_positions)
+    .iter()
+    .enumerate()
+    .filter(|&(_, &taken)| !taken)
+    .map(|(index, _)| tile_at(index, arena))
+    .collect()
+}
+
+// Picks uniformly among the tiles that aren't taken, `None` if there are none.
+// It's the same tile `free_positions(..).choose(..)` would pick, drawing the
+// same number from `rng`, without collecting the free tiles first.
+pub fn random_free_position(
+  rng: &mut GameRng,
+  arena: &ArenaSize,
+  taken_positions: &HashSet<Position>,
+) -> Option<Position> {
+  let taken = taken_tiles(arena, taken_positions);
+  let free_count = taken.iter().filter(|&&taken| !taken).count();
+  if free_count == 0 { return None; }
+  let nth = rng.0.gen_range(0..free_count as u32) as usize;
+  let index = taken.iter().enumerate().filter(|&(_, &taken)| !taken).nth(nth)?.0;
+  Some(tile_at(index, arena))
+}
+
+// The free tile closest to `position` once it's been pulled onto the board,
+// searching outwards ring by ring.
+pub fn nearest_free_position(
+  position: Position,
+  arena: &ArenaSize,
+  taken_positions: &HashSet<Position>,
+) -> Option<Position> {
+  let clamped = Position {
+    x: position.x.max(0).min(arena.width as i32 - 1),
+    y: position.y.max(0).min(arena.height as i32 - 1),
+  };
+  let max_radius = arena.width.max(arena.height) as i32;
+  (0..=max_radius).find_map(|radius| {
+    (-radius..=radius)
+      .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+      .filter(|&(dx, dy)| dx.abs() + dy.abs() == radius)
+      .map(|(dx, dy)| Position { x: clamped.x + dx, y: clamped.y + dy })
+      .find(|candidate| candidate.in_arena(arena) && !taken_positions.contains(candidate))
+  })
+}
+
+// Pulls a snake, head first, back onto a board that's shrunk under it. The head
+// goes to the nearest free tile, and every segment after it that isn't next to
+// the one before anymore goes to whichever free neighbor of that one is closest
+// to where it was, so the body stays in one piece. Whatever can't be fitted on
+// is cut off the tail end, and an empty result means not even the head fit.
+// Every tile used is added to `taken`.
+pub fn reanchor_snake(
+  body: &[Position],
+  arena: &ArenaSize,
+  taken: &mut HashSet<Position>,
+) -> Vec<Position> {
+  let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+  let distance = |a: &Position, b: &Position| (a.x - b.x).abs() + (a.y - b.y).abs();
+  let mut placed: Vec<Position> = Vec::with_capacity(body.len());
+  for &position in body.iter() {
+    let next = match placed.last() {
+      None if position.in_arena(arena) => Some(position),
+      None => nearest_free_position(position, arena, taken),
+      Some(previous) if position.in_arena(arena) && distance(&position, previous) == 1 => {
+        Some(position)
+      }
+      Some(&previous) => directions
+        .iter()
+        .map(|&direction| previous + direction)
+        .filter(|candidate| candidate.in_arena(arena) && !taken.contains(candidate))
+        .min_by_key(|candidate| distance(candidate, &position)),
+    };
+    match next {
+      Some(next) => {
+        taken.insert(next);
+        placed.push(next);
+      }
+      None => break,
+    }
+  }
+  placed
+}
+
+// Handles `ArenaSize` changing mid-game. Walls and portals that end up off the
+// board are removed. Food and power-ups left outside are moved to the nearest
+// free tile, and snakes are pulled back on whole by `reanchor_snake`, so a
+// smaller board never strands a tail or a piece of food where it can't be
+// reached. A snake only loses the segments there's no room left for, and a
+// player's snake with no room even for its head crashes. Everything moved snaps
+// there rather than sliding.
+pub fn reanchor_to_arena(
+  mut commands: Commands,
+  arena: Res<ArenaSize>,
+  mut segments: ResMut<SnakeSegments>,
+  mut ai: ResMut<AiSnakeState>,
+  mut last_tail_position: ResMut<LastTailPosition>,
+  mut tiles: Query<
+    (Entity, &mut Position, Option<&mut PrevPosition>, Option<&Wall>, Option<&Portal>),
+    (Occupying, Without<SnakeSegment>),
+  >,
+  mut snake_tiles: Query<(&mut Position, Option<&mut PrevPosition>), With<SnakeSegment>>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+) {
+  if !arena.is_changed() || arena.is_added() { return; }
+  // Growth falls back to the current tail when there's no last tail position.
+  last_tail_position.0.retain(|_, position| position.in_arena(&arena));
+  if ai.last_tail_position.map_or(false, |position| !position.in_arena(&arena)) {
+    ai.last_tail_position = None;
+  }
+  let mut taken = tiles
+    .iter_mut()
+    .map(|(_, position, ..)| *position)
+    .chain(snake_tiles.iter_mut().map(|(position, _)| *position))
+    .filter(|position| position.in_arena(&arena))
+    .collect::<HashSet<Position>>();
+  let mut snakes = segments.0
+    .iter_mut()
+    .map(|(&player, entities)| (Some(player), entities))
+    .collect::<Vec<(Option<Player>, &mut Vec<Entity>)>>();
+  snakes.push((None, &mut ai.segments));
+  for (player, entities) in snakes {
+    let body = entities
+      .iter()
+      .filter_map(|&entity| snake_tiles.get_mut(entity).ok().map(|(position, _)| *position))
+      .collect::<Vec<Position>>();
+    if body.len() != entities.len() || body.iter().all(|position| position.in_arena(&arena)) {
+      continue;
+    }
+    let placed = reanchor_snake(&body, &arena, &mut taken);
+    if placed.is_empty() {
+      warn!("No room left on the resized board for a snake");
+      if let Some(player) = player {
+        crash_writer.send(SnakeCrashed { player });
+      }
+      continue;
+    }
+    for &entity in entities.iter().skip(placed.len()) {
+      commands.entity(entity).despawn();
+    }
+    entities.truncate(placed.len());
+    for (&entity, &free) in entities.iter().zip(placed.iter()) {
+      let (mut position, prev) = match snake_tiles.get_mut(entity) {
+        Ok(tile) => tile,
+        Err(_) => continue,
+      };
+      if *position == free { continue; }
+      *position = free;
+      if let Some(mut prev) = prev {
+        *prev = PrevPosition::new(free);
+      }
+    }
+  }
+  for (entity, mut position, prev, wall, portal) in tiles.iter_mut() {
+    if position.in_arena(&arena) { continue; }
+    if wall.is_some() || portal.is_some() {
+      commands.entity(entity).despawn();
+      continue;
+    }
+    match nearest_free_position(*position, &arena, &taken) {
+      Some(free) => {
+        taken.insert(free);
+        *position = free;
+        if let Some(mut prev) = prev {
+          *prev = PrevPosition::new(free);
+        }
+      }
+      None => {
+        warn!("No room left on the resized board for {:?}", entity);
+        commands.entity(entity).despawn();
+      }
+    }
+  }
+}
+
+// Tops the board up to `FoodCount` food, not counting bonus food. Once there's
+// no food left and no free tile to put any on, the snake has filled the board
+// and won.
+pub fn food_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  food_count: Res<FoodCount>,
+  growth: Res<FoodGrowth>,
+  lifetime: Res<FoodLifetime>,
+  rot_time: Res<RotTime>,
+  mut rng: ResMut<GameRng>,
+  mut placement: ResMut<FoodPlacement>,
+  food: Query<Entity, (With<Food>, Without<BonusFood>)>,
+  occupied: Query<&Position, Occupying>,
+  heads: Query<&Position, (With<SnakeHead>, With<Player>)>,
+  solid: Query<&Position, Or<(With<SnakeSegment>, With<Wall>)>>,
+  mut game_won_writer: EventWriter<GameWonEvent>,
+) {
+  let current = food.iter().count() as u32;
+  if current >= food_count.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  let mut free = free_positions(&arena, &taken_positions);
+  placement.0.observe(&heads.iter().copied().collect::<Vec<Position>>());
+  for _ in current..food_count.0 {
+    let picked = if free.is_empty() { None } else { Some(placement.0.pick(&free, &mut rng.0)) };
+    match picked {
+      Some(position) => {
+        let food = spawn_food(&mut commands, materials.food_material.clone(), position);
+        commands
+          .entity(food)
+          .insert(GrowthAmount(growth.0))
+          .insert(Lifetime(Timer::from_seconds(lifetime.0, false)));
+        if let Some(rot_time) = rot_time.0 {
+          commands.entity(food).insert(RottenTimer(Timer::from_seconds(rot_time, false)));
+        }
+        free.retain(|&free_position| free_position != position);
+      }
+      None => {
+        if board_filled(&arena, solid.iter()) {
+          game_won_writer.send(GameWonEvent);
+        }
+        return;
+      }
+    }
+  }
+}
+
+// Only one poison food is on the board at a time.
+pub fn poison_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<PoisonChance>,
+  poison: Query<Entity, With<PoisonFood>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if poison.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  let position = match random_free_position(&mut rng, &arena, &taken_positions) {
+    Some(position) => position,
+    None => return,
+  };
+  commands
+    .spawn_bundle(SpriteBundle {
+      material: materials.poison_material.clone(),
+      ..Default::default()
+    })
+    .insert(PoisonFood)
+    .insert(position)
+    .insert(Size::square(0.8));
+}
+
+// Only one bonus food is on the board at a time.
+pub fn bonus_food_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<BonusChance>,
+  bonus: Query<Entity, With<BonusFood>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if bonus.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  let position = match random_free_position(&mut rng, &arena, &taken_positions) {
+    Some(position) => position,
+    None => return,
+  };
+  let food = spawn_food(&mut commands, materials.bonus_material.clone(), position);
+  commands
+    .entity(food)
+    .insert(BonusFood(Timer::from_seconds(BONUS_FOOD_LIFETIME, false)));
+}
+
+// Only one big food is on the board at a time, and only where its whole 2x2
+// footprint is free. Boards without such a spot just don't get one.
+pub fn big_food_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<BigFoodChance>,
+  big_food: Query<Entity, With<BigFood>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if big_food.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  let footprint = |x: i32, y: i32| {
+    [
+      Position { x, y },
+      Position { x: x + 1, y },
+      Position { x, y: y + 1 },
+      Position { x: x + 1, y: y + 1 },
+    ]
+  };
+  let corners = (0..arena.height as i32 - 1)
+    .flat_map(|y| (0..arena.width as i32 - 1).map(move |x| (x, y)))
+    .filter(|&(x, y)| footprint(x, y).iter().all(|position| !taken_positions.contains(position)))
+    .collect::<Vec<(i32, i32)>>();
+  let &(x, y) = match corners.choose(&mut rng.0) {
+    Some(corner) => corner,
+    None => return,
+  };
+  let tiles = footprint(x, y);
+  let food = commands.spawn().insert(BigFood { bites_remaining: tiles.len() as u32 }).id();
+  for &position in tiles.iter() {
+    commands
+      .spawn_bundle(SpriteBundle {
+        material: materials.big_food_material.clone(),
+        ..Default::default()
+      })
+      .insert(BigFoodPart(food))
+      .insert(position)
+      .insert(Size::square(0.9));
+  }
+}
+
+pub fn bonus_food_expiry(
+  mut commands: Commands,
+  countdown: Res<Countdown>,
+  mut bonus: Query<(Entity, &mut BonusFood)>,
+) {
+  if !countdown.0.finished() { return; }
+  for (entity, mut bonus_food) in bonus.iter_mut() {
+    if bonus_food.0.tick(Duration::from_secs_f64(SIMULATION_STEP)).finished() {
+      commands.entity(entity).despawn();
+    }
+  }
+}
+
+// Food under a head is being eaten this frame, so it's left for `snake_eating`
+// to despawn rather than expiring as well.
+pub fn food_expiry(
+  mut commands: Commands,
+  countdown: Res<Countdown>,
+  mut food: Query<(Entity, &Position, &mut Lifetime)>,
+  heads: Query<&Position, With<SnakeHead>>,
+) {
+  if !countdown.0.finished() { return; }
+  for (entity, position, mut lifetime) in food.iter_mut() {
+    if !lifetime.0.tick(Duration::from_secs_f64(SIMULATION_STEP)).finished() { continue; }
+    if heads.iter().all(|head_position| head_position != position) {
+      commands.entity(entity).despawn();
+    }
+  }
+}
+
+// Like with expiry, food that's being eaten this frame doesn't rot.
+pub fn food_rotting(
+  mut commands: Commands,
+  countdown: Res<Countdown>,
+  materials: Res<Materials>,
+  mut food: Query<(Entity, &Position, &mut RottenTimer, &mut Handle<ColorMaterial>), With<Food>>,
+  heads: Query<&Position, With<SnakeHead>>,
+) {
+  if !countdown.0.finished() { return; }
+  for (entity, position, mut timer, mut material) in food.iter_mut() {
+    if !timer.0.tick(Duration::from_secs_f64(SIMULATION_STEP)).finished() { continue; }
+    if heads.iter().any(|head_position| head_position == position) { continue; }
+    *material = materials.rotten_material.clone();
+    commands
+      .entity(entity)
+      .remove::<Food>()
+      .remove::<RottenTimer>()
+      .insert(Rotten)
+      .insert(Wall);
+  }
+}
+
+// Places the food from the level. `food_spawner` adds any more that's needed
+// to reach `FoodCount` once the board has been set up.
+pub fn spawn_initial_food(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  level: Res<LevelData>,
+  growth: Res<FoodGrowth>,
+) {
+  for &position in level.food.iter() {
+    let food = spawn_food(&mut commands, materials.food_material.clone(), position);
+    commands.entity(food).insert(GrowthAmount(growth.0));
+  }
+}
+
+pub fn spawn_food(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  position: Position,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(Food)
+    .insert(position)
+    .insert(Size::square(0.8))
+    .id()
+}
+
+#[cfg(test)]
+mod tests {
+  use bevy::ecs::schedule::SystemDescriptor;
+  use bevy::ecs::system::CommandQueue;
+  use bevy::window::WindowId;
+
+  use super::*;
+
+  fn test_world() -> World {
+    let mut world = World::default();
+    world.insert_resource(Time::default());
+    world.insert_resource(MinLength::default());
+    world.insert_resource(SnakeSegments::default());
+    world.insert_resource(Events::<GrowthEvent>::default());
+    world.insert_resource(Events::<ShrinkEvent>::default());
+    world.insert_resource(Events::<ScoreEvent>::default());
+    world.insert_resource(Events::<SnakeCrashed>::default());
+    world.insert_resource(Events::<GameOverEvent>::default());
+    world.insert_resource(Events::<LifeLostEvent>::default());
+    world
+  }
+
+  // Kept across frames, so event readers don't see the same events twice.
+  fn test_stage(systems: Vec<SystemDescriptor>) -> SystemStage {
+    let mut stage = SystemStage::single_threaded();
+    for system in systems {
+      stage.add_system(system);
+    }
+    stage
+  }
+
+  // Player one's snake going up column 0, head at the top.
+  fn spawn_test_snake(world: &mut World, length: i32) {
+    let mut entities = Vec::new();
+    for y in (0..length).rev() {
+      let mut segment = world.spawn();
+      segment.insert(Position::new(0, y)).insert(SnakeSegment).insert(Player::One);
+      if y == length - 1 {
+        segment.insert(SnakeHead::new(Direction::Up));
+      }
+      entities.push(segment.id());
+    }
+    world.get_resource_mut::<SnakeSegments>().unwrap().insert(Player::One, entities);
+  }
+
+  fn snake_length(world: &World) -> usize {
+    world.get_resource::<SnakeSegments>().unwrap().len(Player::One)
+  }
+
+  fn sent<T: Send + Sync + 'static>(world: &World) -> usize {
+    world.get_resource::<Events<T>>().unwrap().iter_current_update_events().count()
+  }
+
+  fn finished_timer() -> Timer {
+    let mut timer = Timer::from_seconds(0.0, false);
+    timer.tick(Duration::default());
+    timer
+  }
+
+  fn rules(width: u32, height: u32) -> MovementRules {
+    MovementRules {
+      arena: ArenaSize { width, height },
+      edges: EdgeConfig::default(),
+      walls: Vec::new(),
+      portals: Vec::new(),
+      fatal_blocked_portals: false,
+    }
+  }
+
+  fn positions(tiles: &[(i32, i32)]) -> Vec<Position> {
+    tiles.iter().map(|&(x, y)| Position::new(x, y)).collect()
+  }
+
+  #[test]
+  fn moving_shifts_every_segment_up_one() {
+    let snake = positions(&[(2, 2), (2, 1), (2, 0)]);
+    let step = move_snake(&snake, Direction::Right, &snake[..2], false, &rules(5, 5));
+    assert_eq!(step.positions, positions(&[(3, 2), (2, 2), (2, 1)]));
+    assert_eq!(step.direction, Direction::Right);
+    assert!(!step.game_over);
+  }
+
+  #[test]
+  fn growing_goes_where_the_tail_was() {
+    let snake = positions(&[(2, 2), (2, 1)]);
+    let step = move_snake(&snake, Direction::Up, &snake[..1], false, &rules(5, 5));
+    assert_eq!(step.grow_at, Position::new(2, 1));
+    assert!(!step.positions.contains(&step.grow_at));
+  }
+
+  #[test]
+  fn running_into_an_edge_or_wall_is_fatal() {
+    let snake = positions(&[(4, 2), (3, 2)]);
+    assert!(move_snake(&snake, Direction::Right, &snake[..1], false, &rules(5, 5)).game_over);
+    let mut walled = rules(5, 5);
+    walled.walls.push(Position::new(4, 3));
+    assert!(move_snake(&snake, Direction::Up, &snake[..1], false, &walled).game_over);
+  }
+
+  #[test]
+  fn running_into_itself_is_fatal_unless_a_ghost() {
+    // Turning back on itself around a corner.
+    let snake = positions(&[(2, 2), (2, 1), (1, 1), (1, 2), (1, 3)]);
+    let bodies = &snake[..4];
+    assert!(move_snake(&snake, Direction::Left, bodies, false, &rules(5, 5)).game_over);
+    assert!(!move_snake(&snake, Direction::Left, bodies, true, &rules(5, 5)).game_over);
+  }
+
+  fn mixed_edges() -> MovementRules {
+    let mut mixed = rules(5, 5);
+    mixed.edges = EdgeConfig {
+      top: WallBehavior::Bounce,
+      bottom: WallBehavior::Die,
+      left: WallBehavior::Wrap,
+      right: WallBehavior::Die,
+    };
+    mixed
+  }
+
+  #[test]
+  fn each_edge_does_its_own_thing() {
+    let snake = positions(&[(0, 2), (1, 2)]);
+    let step = move_snake(&snake, Direction::Left, &snake[..1], false, &mixed_edges());
+    assert_eq!(step.positions, positions(&[(4, 2), (0, 2)]));
+    assert!(!step.game_over);
+
+    let snake = positions(&[(4, 2), (3, 2)]);
+    assert!(move_snake(&snake, Direction::Right, &snake[..1], false, &mixed_edges()).game_over);
+    let snake = positions(&[(2, 0), (2, 1)]);
+    assert!(move_snake(&snake, Direction::Down, &snake[..1], false, &mixed_edges()).game_over);
+
+    let snake = positions(&[(2, 4), (2, 3), (2, 2)]);
+    let step = move_snake(&snake, Direction::Up, &snake[..2], false, &mixed_edges());
+    assert_eq!(step.positions, positions(&[(2, 2), (2, 3), (2, 4)]));
+    assert_eq!(step.direction, Direction::Down);
+    assert!(!step.game_over);
+  }
+
+  #[test]
+  fn each_wall_behavior_at_the_right_edge() {
+    let mut edges = rules(5, 5);
+    let snake = positions(&[(4, 2), (3, 2), (2, 2)]);
+
+    edges.edges = EdgeConfig::all(WallBehavior::Die);
+    assert!(move_snake(&snake, Direction::Right, &snake[..2], false, &edges).game_over);
+
+    edges.edges = EdgeConfig::all(WallBehavior::Wrap);
+    let step = move_snake(&snake, Direction::Right, &snake[..2], false, &edges);
+    assert_eq!(step.positions, positions(&[(0, 2), (4, 2), (3, 2)]));
+    assert_eq!(step.direction, Direction::Right);
+    assert!(!step.game_over);
+
+    // Bouncing turns the snake around, so the old tail leads the way back.
+    edges.edges = EdgeConfig::all(WallBehavior::Bounce);
+    let step = move_snake(&snake, Direction::Right, &snake[..2], false, &edges);
+    assert_eq!(step.positions, positions(&[(2, 2), (3, 2), (4, 2)]));
+    assert_eq!(step.direction, Direction::Left);
+    assert!(!step.game_over);
+  }
+
+  #[test]
+  fn wrapping_onto_a_wall_is_fatal() {
+    let mut walled = mixed_edges();
+    walled.walls.push(Position::new(4, 2));
+    let snake = positions(&[(0, 2), (1, 2)]);
+    assert!(move_snake(&snake, Direction::Left, &snake[..1], false, &walled).game_over);
+  }
+
+  fn spawn_at(x: i32, y: i32, direction: Direction) -> SnakeSpawnConfig {
+    SnakeSpawnConfig { start: Position::new(x, y), length: 1, direction }
+  }
+
+  #[test]
+  fn spawns_in_a_corner_turn_to_face_the_board() {
+    let arena = ArenaSize { width: 5, height: 5 };
+    let directions = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+    for &(x, y) in [(0, 0), (0, 4), (4, 0), (4, 4)].iter() {
+      for &direction in directions.iter() {
+        let spawn = spawn_at(x, y, direction).facing_safely(&arena, &EdgeConfig::default(), &[]);
+        assert!((spawn.start + spawn.direction).in_arena(&arena), "({}, {}) {:?}", x, y, direction);
+      }
+    }
+  }
+
+  #[test]
+  fn spawn_boxed_in_front_and_behind_turns_aside() {
+    let arena = ArenaSize { width: 5, height: 5 };
+    let edges = EdgeConfig::default();
+    let walls = [Position::new(1, 4)];
+    let spawn = spawn_at(0, 4, Direction::Left).facing_safely(&arena, &edges, &walls);
+    assert_eq!(spawn.direction, Direction::Down);
+  }
+
+  #[test]
+  fn spawn_facing_a_wrapping_edge_is_left_alone() {
+    let arena = ArenaSize { width: 5, height: 5 };
+    let edges = EdgeConfig { left: WallBehavior::Wrap, ..EdgeConfig::default() };
+    let spawn = spawn_at(0, 4, Direction::Left).facing_safely(&arena, &edges, &[]);
+    assert_eq!(spawn.direction, Direction::Left);
+    let spawn = spawn_at(0, 4, Direction::Up).facing_safely(&arena, &edges, &[]);
+    assert_eq!(spawn.direction, Direction::Down);
+    let walls = [Position::new(4, 4)];
+    let spawn = spawn_at(0, 4, Direction::Left).facing_safely(&arena, &edges, &walls);
+    assert_eq!(spawn.direction, Direction::Right);
+  }
+
+  #[test]
+  fn poison_shrinks_down_to_the_floor_then_crashes() {
+    let mut world = test_world();
+    let min_length = world.get_resource::<MinLength>().unwrap().0;
+    spawn_test_snake(&mut world, min_length as i32 + 1);
+    let mut stage = test_stage(vec![
+      snake_eating.system().label("eating").into(),
+      snake_shrink.system().after("eating").into(),
+    ]);
+    for expected_crashes in 0..2 {
+      let head = Position::new(0, min_length as i32);
+      world.spawn().insert(PoisonFood).insert(head);
+      stage.run(&mut world);
+      assert_eq!(snake_length(&world), min_length);
+      assert_eq!(sent::<SnakeCrashed>(&world), expected_crashes);
+    }
+  }
+
+  #[test]
+  fn hunger_shrinks_down_to_the_floor_then_crashes() {
+    let mut world = test_world();
+    let min_length = world.get_resource::<MinLength>().unwrap().0;
+    spawn_test_snake(&mut world, min_length as i32 + 1);
+    world.insert_resource(Countdown(finished_timer()));
+    world.insert_resource(HungerPenalty::Shrink);
+    let mut stage = test_stage(vec![
+      hunger.system().label("hunger").into(),
+      snake_shrink.system().after("hunger").into(),
+    ]);
+    for expected_crashes in 0..2 {
+      // Runs out the moment it's ticked.
+      world.insert_resource(Hunger(Timer::from_seconds(0.0, false)));
+      stage.run(&mut world);
+      assert_eq!(snake_length(&world), min_length);
+      assert_eq!(sent::<SnakeCrashed>(&world), expected_crashes);
+    }
+    assert_eq!(sent::<GameOverEvent>(&world), 0);
+  }
+
+  #[test]
+  fn boost_stops_draining_at_the_floor() {
+    let mut world = test_world();
+    let min_length = world.get_resource::<MinLength>().unwrap().0;
+    spawn_test_snake(&mut world, min_length as i32 + 2);
+    let boost_key = BoostKey::default();
+    let mut input = Input::<KeyCode>::default();
+    input.press(boost_key.0);
+    world.insert_resource(input);
+    world.insert_resource(boost_key);
+    world.insert_resource(Boosting::default());
+    world.insert_resource(Countdown(finished_timer()));
+    world.insert_resource(GameStats::default());
+    world.insert_resource(ReplayMode::default());
+    world.insert_resource(Replay::default());
+    let mut stage = test_stage(vec![boost.system().into()]);
+    for _ in 0..4 {
+      // Drains on every frame it's held.
+      world.insert_resource(BoostDrain(Timer::from_seconds(0.0, false)));
+      stage.run(&mut world);
+    }
+    assert_eq!(snake_length(&world), min_length);
+    assert!(!world.get_resource::<Boosting>().unwrap().0);
+    assert_eq!(sent::<SnakeCrashed>(&world), 0);
+  }
+
+  #[test]
+  fn letting_go_of_boost_at_the_speed_floor_goes_back_to_the_floor() {
+    let mut world = test_world();
+    let speed_config = SpeedConfig::default();
+    let floor = speed_config.floor;
+    world.insert_resource(BaseInterval(floor));
+    world.insert_resource(speed_config);
+    world.insert_resource(Boosting(true));
+    world.insert_resource(SlowMo::default());
+    world.insert_resource(MovementTimer::default());
+    let mut stage = test_stage(vec![
+      increase_speed.system().label("increase_speed").into(),
+      movement_interval.system().after("increase_speed").into(),
+    ]);
+    world.get_resource_mut::<Events<GrowthEvent>>().unwrap().send(GrowthEvent {
+      player: Player::One,
+      amount: 1,
+    });
+    stage.run(&mut world);
+    assert_eq!(world.get_resource::<MovementTimer>().unwrap().0.duration(), floor / 2);
+
+    world.get_resource_mut::<Boosting>().unwrap().0 = false;
+    stage.run(&mut world);
+    assert_eq!(world.get_resource::<BaseInterval>().unwrap().0, floor);
+    assert_eq!(world.get_resource::<MovementTimer>().unwrap().0.duration(), floor);
+  }
+
+  fn in_one_piece(body: &[Position]) -> bool {
+    body.windows(2).all(|pair| {
+      (pair[0].x - pair[1].x).abs() + (pair[0].y - pair[1].y).abs() == 1
+    })
+  }
+
+  #[test]
+  fn shrinking_folds_a_stranded_tail_back_onto_the_board() {
+    let arena = ArenaSize { width: 6, height: 10 };
+    let body = [Position::new(4, 5), Position::new(5, 5), Position::new(6, 5), Position::new(7, 5)];
+    let mut taken = body.iter().copied().filter(|position| position.in_arena(&arena)).collect();
+    let placed = reanchor_snake(&body, &arena, &mut taken);
+    assert_eq!(placed.len(), body.len());
+    assert_eq!(&placed[..2], &body[..2]);
+    assert!(placed.iter().all(|position| position.in_arena(&arena)));
+    assert!(in_one_piece(&placed));
+    assert_eq!(placed.iter().collect::<HashSet<&Position>>().len(), placed.len());
+  }
+
+  #[test]
+  fn shrinking_cuts_off_what_doesnt_fit() {
+    let arena = ArenaSize { width: 2, height: 1 };
+    let body = [Position::new(0, 0), Position::new(1, 0), Position::new(2, 0)];
+    let mut taken = body[..2].iter().copied().collect();
+    assert_eq!(reanchor_snake(&body, &arena, &mut taken), body[..2].to_vec());
+  }
+
+  #[test]
+  fn shrinking_moves_a_head_left_outside() {
+    let arena = ArenaSize { width: 5, height: 5 };
+    let body = [Position::new(7, 2), Position::new(8, 2)];
+    let mut taken = HashSet::new();
+    let placed = reanchor_snake(&body, &arena, &mut taken);
+    assert_eq!(placed[0], Position::new(4, 2));
+    assert_eq!(placed.len(), 2);
+    assert!(in_one_piece(&placed));
+  }
+
+  fn growth_world() -> World {
+    let mut world = test_world();
+    world.insert_resource(Materials::default());
+    world.insert_resource(VisualConfig::default());
+    world.insert_resource(LastTailPosition::default());
+    world
+  }
+
+  fn send_growth(world: &mut World, player: Player, amount: u32) {
+    world.get_resource_mut::<Events<GrowthEvent>>().unwrap().send(GrowthEvent { player, amount });
+  }
+
+  #[test]
+  fn growth_goes_to_the_snake_that_ate() {
+    let mut world = growth_world();
+    spawn_test_snake(&mut world, 2);
+    let two = world
+      .spawn()
+      .insert(Position::new(4, 4))
+      .insert(SnakeSegment)
+      .insert(Player::Two)
+      .insert(SnakeHead::new(Direction::Down))
+      .id();
+    world.get_resource_mut::<SnakeSegments>().unwrap().insert(Player::Two, vec![two]);
+    // Only player one has moved, so player two grows from its own tail.
+    let mut last_tail_position = world.get_resource_mut::<LastTailPosition>().unwrap();
+    last_tail_position.0.insert(Player::One, Position::new(0, -1));
+    let mut stage = test_stage(vec![snake_growth.system().into()]);
+    send_growth(&mut world, Player::Two, 2);
+    stage.run(&mut world);
+
+    assert_eq!(snake_length(&world), 2);
+    let segments = world.get_resource::<SnakeSegments>().unwrap();
+    let two_segments = segments.get(Player::Two).unwrap().to_vec();
+    assert_eq!(two_segments.len(), 3);
+    for segment in two_segments {
+      assert_eq!(world.get::<Position>(segment), Some(&Position::new(4, 4)));
+    }
+  }
+
+  fn food_spawner_world(width: u32, height: u32) -> World {
+    let mut world = test_world();
+    world.insert_resource(Materials::default());
+    world.insert_resource(ArenaSize { width, height });
+    world.insert_resource(FoodCount::default());
+    world.insert_resource(FoodGrowth::default());
+    world.insert_resource(FoodLifetime::default());
+    world.insert_resource(RotTime::default());
+    world.insert_resource(GameRng::from_seed(1));
+    world.insert_resource(FoodPlacement::default());
+    world.insert_resource(Events::<GameWonEvent>::default());
+    world
+  }
+
+  #[test]
+  fn filling_a_two_by_two_board_wins() {
+    let mut world = food_spawner_world(2, 2);
+    for position in positions(&[(0, 0), (0, 1), (1, 1), (1, 0)]) {
+      world.spawn().insert(position).insert(SnakeSegment).insert(Player::One);
+    }
+    test_stage(vec![food_spawner.system().into()]).run(&mut world);
+    assert_eq!(sent::<GameWonEvent>(&world), 1);
+  }
+
+  #[test]
+  fn poison_on_the_last_free_tile_isnt_a_win() {
+    let mut world = food_spawner_world(2, 2);
+    for position in positions(&[(0, 0), (0, 1), (1, 1)]) {
+      world.spawn().insert(position).insert(SnakeSegment).insert(Player::One);
+    }
+    world.spawn().insert(Position::new(1, 0)).insert(PoisonFood);
+    test_stage(vec![food_spawner.system().into()]).run(&mut world);
+    assert_eq!(sent::<GameWonEvent>(&world), 0);
+    assert_eq!(world.query_filtered::<Entity, With<Food>>().iter(&world).count(), 0);
+  }
+
+  #[test]
+  fn spawned_food_grows_by_the_configured_amount() {
+    let mut world = food_spawner_world(5, 5);
+    world.insert_resource(FoodGrowth(3));
+    test_stage(vec![food_spawner.system().into()]).run(&mut world);
+    let amounts = world
+      .query_filtered::<&GrowthAmount, With<Food>>()
+      .iter(&world)
+      .map(|growth| growth.0)
+      .collect::<Vec<u32>>();
+    assert_eq!(amounts, vec![3]);
+  }
+
+  fn input_world(bindings: KeyBindings) -> World {
+    let mut world = World::default();
+    world.insert_resource(Input::<KeyCode>::default());
+    world.insert_resource(bindings);
+    world.insert_resource(AlternateKeyBindings(None));
+    world.insert_resource(ControlScheme::default());
+    world.insert_resource(PlayMode::default());
+    world.insert_resource(ReplayMode::default());
+    world
+  }
+
+  fn spawn_head(world: &mut World, direction: Direction) -> Entity {
+    world.spawn().insert(SnakeHead::new(direction)).insert(Player::One).id()
+  }
+
+  // Presses `key` for one frame and returns where the snake will end up going.
+  fn steer(world: &mut World, head: Entity, key: KeyCode) -> Direction {
+    let mut input = world.get_resource_mut::<Input<KeyCode>>().unwrap();
+    input.clear();
+    input.press(key);
+    test_stage(vec![snake_movement_input.system().into()]).run(world);
+    world.get::<SnakeHead>(head).unwrap().queued_direction()
+  }
+
+  #[test]
+  fn a_rebound_key_steers_and_the_old_one_doesnt() {
+    let mut world = input_world(KeyBindings { left: KeyCode::J, ..KeyBindings::default() });
+    let head = spawn_head(&mut world, Direction::Up);
+    assert_eq!(steer(&mut world, head, KeyCode::Left), Direction::Up);
+    assert_eq!(steer(&mut world, head, KeyCode::J), Direction::Left);
+  }
+
+  // Everything `snake_movement` needs, with the timer going off every step.
+  fn movement_world(width: u32, height: u32) -> World {
+    let mut world = test_world();
+    let step = Duration::from_secs_f64(SIMULATION_STEP);
+    world.insert_resource(MovementTimer(Timer::new(step, true)));
+    world.insert_resource(ArenaSize { width, height });
+    world.insert_resource(EdgeConfig::default());
+    world.insert_resource(LastTailPosition::default());
+    world.insert_resource(FatalBlockedPortals::default());
+    world.insert_resource(Countdown(finished_timer()));
+    world.insert_resource(Invincible::default());
+    world.insert_resource(GameStats::default());
+    world.insert_resource(Events::<DirectionChanged>::default());
+    world
+  }
+
+  fn head_position(world: &World) -> Position {
+    let head = world.get_resource::<SnakeSegments>().unwrap().head(Player::One).unwrap();
+    *world.get::<Position>(head).unwrap()
+  }
+
+  #[test]
+  fn a_tall_narrow_board_goes_all_the_way_up() {
+    let mut world = movement_world(5, 20);
+    spawn_test_snake(&mut world, 2);
+    let mut stage = test_stage(vec![snake_movement.system().into()]);
+    for _ in 0..18 {
+      stage.run(&mut world);
+    }
+    assert_eq!(head_position(&world), Position::new(0, 19));
+    assert_eq!(sent::<SnakeCrashed>(&world), 0);
+    stage.run(&mut world);
+    assert_eq!(sent::<SnakeCrashed>(&world), 1);
+  }
+
+  #[test]
+  fn each_direction_steps_one_tile_its_way() {
+    let start = Position::new(2, 2);
+    assert_eq!(start + Direction::Left, Position::new(1, 2));
+    assert_eq!(start + Direction::Up, Position::new(2, 3));
+    assert_eq!(start + Direction::Right, Position::new(3, 2));
+    assert_eq!(start + Direction::Down, Position::new(2, 1));
+    for &direction in [Direction::Left, Direction::Up, Direction::Right, Direction::Down].iter() {
+      assert_eq!(Direction::between(start, start + direction), Some(direction));
+    }
+  }
+
+  #[test]
+  fn each_arrow_key_steers_its_way() {
+    let keys = [
+      (KeyCode::Left, Direction::Up, Direction::Left),
+      (KeyCode::Right, Direction::Up, Direction::Right),
+      (KeyCode::Up, Direction::Left, Direction::Up),
+      (KeyCode::Down, Direction::Left, Direction::Down),
+    ];
+    for &(key, heading, expected) in keys.iter() {
+      let mut world = input_world(KeyBindings::default());
+      let head = spawn_head(&mut world, heading);
+      assert_eq!(steer(&mut world, head, key), expected);
+    }
+  }
+
+  #[test]
+  fn chasing_the_tail_into_its_old_tile_is_safe() {
+    let mut world = movement_world(4, 4);
+    // A square loop with the head at (0, 1) about to step onto the tail at (0, 0).
+    let mut entities = Vec::new();
+    for &(x, y) in [(0, 1), (1, 1), (1, 0), (0, 0)].iter() {
+      let mut segment = world.spawn();
+      segment.insert(Position::new(x, y)).insert(SnakeSegment).insert(Player::One);
+      if entities.is_empty() {
+        segment.insert(SnakeHead::new(Direction::Down));
+      }
+      entities.push(segment.id());
+    }
+    world.get_resource_mut::<SnakeSegments>().unwrap().insert(Player::One, entities);
+    let mut stage = test_stage(vec![snake_movement.system().into()]);
+    stage.run(&mut world);
+    assert_eq!(head_position(&world), Position::new(0, 0));
+    assert_eq!(sent::<SnakeCrashed>(&world), 0);
+  }
+
+  #[test]
+  fn growing_before_the_first_tick_grows_onto_the_tail() {
+    let mut world = growth_world();
+    spawn_test_snake(&mut world, 2);
+    // Nothing has moved yet, so there's no LastTailPosition to grow at.
+    let mut stage = test_stage(vec![snake_growth.system().into()]);
+    send_growth(&mut world, Player::One, 1);
+    stage.run(&mut world);
+
+    assert_eq!(snake_length(&world), 3);
+    let segments = world.get_resource::<SnakeSegments>().unwrap();
+    let tail = *segments.get(Player::One).unwrap().last().unwrap();
+    assert_eq!(world.get::<Position>(tail), Some(&Position::new(0, 0)));
+  }
+
+  #[test]
+  fn a_wide_window_fits_the_board_to_its_height() {
+    let descriptor = WindowDescriptor { width: 800.0, height: 400.0, ..Default::default() };
+    let window = Window::new(WindowId::primary(), &descriptor, 800, 400, 1.0, None);
+    let square = ArenaSize { width: 10, height: 10 };
+    assert_eq!(board_size(&window, &square, ScalingMode::Fit), Vec2::new(400.0, 400.0));
+    assert_eq!(board_size(&window, &square, ScalingMode::Stretch), Vec2::new(800.0, 400.0));
+    let wide = ArenaSize { width: 20, height: 5 };
+    assert_eq!(board_size(&window, &wide, ScalingMode::Fit), Vec2::new(800.0, 200.0));
+  }
+
+  #[test]
+  fn a_custom_visual_config_sizes_the_snake() {
+    let mut world = World::default();
+    let visual = VisualConfig { head_scale: 1.0, segment_scale: 0.3 };
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &world);
+    let snake = positions(&[(2, 2), (2, 1)]);
+    let materials = Materials::default();
+    let segments =
+      spawn_player_snake(&mut commands, &materials, Player::One, &snake, Direction::Up, &visual);
+    queue.apply(&mut world);
+
+    let head = world.get::<Size>(segments[0]).unwrap();
+    assert_eq!((head.width, head.height), (1.0, 1.0));
+    let body = world.get::<Size>(segments[1]).unwrap();
+    assert_eq!((body.width, body.height), (0.3, 0.3));
+  }
+
+  #[test]
+  fn a_long_hitch_only_catches_up_so_far() {
+    let mut world = World::default();
+    world.insert_resource(Time::default());
+    world.insert_resource(State::new(GameState::Playing));
+    world.insert_resource(SimulationClock::default());
+    world.insert_resource(GameStats::default());
+    world.get_resource_mut::<SimulationClock>().unwrap().advance(100);
+    fn count_step(mut stats: ResMut<GameStats>) {
+      stats.steps_simulated += 1;
+    }
+    let mut stage = SystemStage::single_threaded().with_run_criteria(run_simulation.system());
+    stage.add_system(count_step.system());
+    stage.run(&mut world);
+    let stats = world.get_resource::<GameStats>().unwrap();
+    assert_eq!(stats.steps_simulated, MAX_CATCH_UP_STEPS as u64);
+  }
 }
@@ -0,0 +1,82 @@
+use bevy::input::gamepad::{
+  Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, GamepadEvent,
+  GamepadEventType,
+};
+use bevy::prelude::*;
+
+use crate::game::{steer_player_one, Direction, Player, SnakeHead};
+use crate::replay::ReplayMode;
+
+// Stick movements smaller than this are ignored.
+pub const STICK_DEAD_ZONE: f32 = 0.5;
+
+// Every gamepad currently plugged in, in the order they were connected.
+#[derive(Default)]
+pub struct ConnectedGamepads(pub Vec<Gamepad>);
+
+// The gamepad that steers player one. With none picked, or the picked one
+// unplugged, it's the first one connected.
+#[derive(Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+pub fn gamepad_connections(
+  mut gamepad_reader: EventReader<GamepadEvent>,
+  mut connected: ResMut<ConnectedGamepads>,
+) {
+  for GamepadEvent(gamepad, event_type) in gamepad_reader.iter() {
+    match event_type {
+      GamepadEventType::Connected if !connected.0.contains(gamepad) => connected.0.push(*gamepad),
+      GamepadEventType::Disconnected => connected.0.retain(|connected| connected != gamepad),
+      _ => {}
+    }
+  }
+}
+
+// The stick points whichever way it's pushed furthest.
+pub fn stick_direction(x: f32, y: f32) -> Option<Direction> {
+  if x.abs().max(y.abs()) < STICK_DEAD_ZONE {
+    None
+  } else if x.abs() > y.abs() {
+    Some(if x > 0.0 { Direction::Right } else { Direction::Left })
+  } else {
+    Some(if y > 0.0 { Direction::Up } else { Direction::Down })
+  }
+}
+
+// The D-pad takes priority over the left stick. A held stick keeps asking for
+// the same direction, which `queue_direction` simply ignores.
+pub fn gamepad_input(
+  connected: Res<ConnectedGamepads>,
+  active: Res<ActiveGamepad>,
+  replay_mode: Res<ReplayMode>,
+  buttons: Res<Input<GamepadButton>>,
+  axes: Res<Axis<GamepadAxis>>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+) {
+  if *replay_mode == ReplayMode::Playback { return; }
+  let gamepad = match active.0.filter(|gamepad| connected.0.contains(gamepad)) {
+    Some(gamepad) => gamepad,
+    None => match connected.0.first() {
+      Some(&gamepad) => gamepad,
+      None => return,
+    },
+  };
+  let dpad = [
+    (GamepadButtonType::DPadUp, Direction::Up),
+    (GamepadButtonType::DPadDown, Direction::Down),
+    (GamepadButtonType::DPadLeft, Direction::Left),
+    (GamepadButtonType::DPadRight, Direction::Right),
+  ];
+  let pressed = dpad
+    .iter()
+    .find(|(button, _)| buttons.just_pressed(GamepadButton(gamepad, *button)))
+    .map(|&(_, direction)| direction);
+  let stick = || {
+    let x = axes.get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))?;
+    let y = axes.get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))?;
+    stick_direction(x, y)
+  };
+  if let Some(direction) = pressed.or_else(stick) {
+    steer_player_one(&mut heads, direction);
+  }
+}
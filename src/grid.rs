@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+use crate::game::{board_size, convert_dimension, ArenaSize, Materials, ScalingMode};
+
+pub const GRID_LINE_THICKNESS: f32 = 1.0;
+// Just behind the tiles, which are drawn at z = 0.
+pub const GRID_Z: f32 = -0.05;
+
+// A line between two columns (`vertical`) or two rows of tiles. `index` is the
+// column or row the line is on the left of or below.
+pub struct GridLine {
+  pub vertical: bool,
+  pub index: u32,
+}
+
+pub struct GridVisible(pub bool);
+
+// One of the four sides of the frame around the board. The frame is drawn just
+// inside the board's edge, so it stays visible when the board fills the window.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BorderEdge {
+  Top,
+  Bottom,
+  Left,
+  Right,
+}
+
+// How thick the frame around the board is, in pixels.
+pub struct BorderThickness(pub f32);
+
+impl Default for BorderThickness {
+  fn default() -> Self {
+    Self(3.0)
+  }
+}
+
+impl Default for GridVisible {
+  fn default() -> Self {
+    Self(true)
+  }
+}
+
+// One line between every two columns and every two rows.
+fn spawn_grid_lines(
+  commands: &mut Commands,
+  material: &Handle<ColorMaterial>,
+  arena: &ArenaSize,
+  visible: bool,
+) {
+  for (vertical, count) in [(true, arena.width), (false, arena.height)].iter().copied() {
+    for index in 1..count {
+      commands
+        .spawn_bundle(SpriteBundle {
+          material: material.clone(),
+          visible: Visible { is_visible: visible, is_transparent: false },
+          ..Default::default()
+        })
+        .insert(GridLine { vertical, index });
+    }
+  }
+}
+
+pub fn spawn_grid(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  grid_visible: Res<GridVisible>,
+) {
+  spawn_grid_lines(&mut commands, &materials.grid_material, &arena, grid_visible.0);
+}
+
+// A resized board needs a different number of lines, so they're all replaced.
+pub fn rebuild_grid(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  grid_visible: Res<GridVisible>,
+  lines: Query<Entity, With<GridLine>>,
+) {
+  if !arena.is_changed() || arena.is_added() { return; }
+  for entity in lines.iter() {
+    commands.entity(entity).despawn();
+  }
+  spawn_grid_lines(&mut commands, &materials.grid_material, &arena, grid_visible.0);
+}
+
+pub fn spawn_border(mut commands: Commands, materials: Res<Materials>) {
+  for &edge in [BorderEdge::Top, BorderEdge::Bottom, BorderEdge::Left, BorderEdge::Right].iter() {
+    commands
+      .spawn_bundle(SpriteBundle {
+        material: materials.border_material.clone(),
+        ..Default::default()
+      })
+      .insert(edge);
+  }
+}
+
+pub fn grid_layout(
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  mut lines: Query<(&GridLine, &mut Sprite, &mut Transform)>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  for (line, mut sprite, mut transform) in lines.iter_mut() {
+    // Lines sit on the edge between two tiles, half a tile before `index`.
+    let edge = line.index as f32 - 0.5;
+    if line.vertical {
+      sprite.size = Vec2::new(GRID_LINE_THICKNESS, board.y);
+      transform.translation = Vec3::new(
+        convert_dimension(edge, board.x, arena.width as f32),
+        0.0,
+        GRID_Z,
+      );
+    } else {
+      sprite.size = Vec2::new(board.x, GRID_LINE_THICKNESS);
+      transform.translation = Vec3::new(
+        0.0,
+        convert_dimension(edge, board.y, arena.height as f32),
+        GRID_Z,
+      );
+    }
+  }
+}
+
+// The outer edges of the board are half a tile beyond the first and last rows
+// and columns, the same place the snake dies on.
+pub fn border_layout(
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  thickness: Res<BorderThickness>,
+  mut edges: Query<(&BorderEdge, &mut Sprite, &mut Transform)>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  let left = convert_dimension(-0.5, board.x, arena.width as f32);
+  let right = convert_dimension(arena.width as f32 - 0.5, board.x, arena.width as f32);
+  let bottom = convert_dimension(-0.5, board.y, arena.height as f32);
+  let top = convert_dimension(arena.height as f32 - 0.5, board.y, arena.height as f32);
+  let inset = thickness.0 / 2.0;
+  for (edge, mut sprite, mut transform) in edges.iter_mut() {
+    let (size, x, y) = match edge {
+      BorderEdge::Top => (Vec2::new(board.x, thickness.0), 0.0, top - inset),
+      BorderEdge::Bottom => (Vec2::new(board.x, thickness.0), 0.0, bottom + inset),
+      BorderEdge::Left => (Vec2::new(thickness.0, board.y), left + inset, 0.0),
+      BorderEdge::Right => (Vec2::new(thickness.0, board.y), right - inset, 0.0),
+    };
+    sprite.size = size;
+    transform.translation = Vec3::new(x, y, GRID_Z);
+  }
+}
+
+pub fn toggle_grid(input: Res<Input<KeyCode>>, mut grid_visible: ResMut<GridVisible>) {
+  if input.just_pressed(KeyCode::G) {
+    grid_visible.0 = !grid_visible.0;
+  }
+}
+
+pub fn grid_visibility(
+  grid_visible: Res<GridVisible>,
+  mut lines: Query<&mut Visible, With<GridLine>>,
+) {
+  if !grid_visible.is_changed() { return; }
+  for mut visible in lines.iter_mut() {
+    visible.is_visible = grid_visible.0;
+  }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{GameOverEvent, GameStats, GameWonEvent, PlayMode};
+
+pub const HIGH_SCORE_FILE: &str = "highscore.txt";
+pub const RECORDS_FILE: &str = "records.ron";
+
+pub struct HighScore(pub u32);
+
+// The best of every run so far, for more to aim at than the points alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Records {
+  pub best_score: u32,
+  pub best_length: usize,
+  pub best_ticks: u64,
+}
+
+fn data_path(file: &str) -> PathBuf {
+  std::env::current_exe()
+    .ok()
+    .and_then(|exe| exe.parent().map(|dir| dir.join(file)))
+    .unwrap_or_else(|| PathBuf::from(file))
+}
+
+pub fn high_score_path() -> PathBuf {
+  data_path(HIGH_SCORE_FILE)
+}
+
+pub fn records_path() -> PathBuf {
+  data_path(RECORDS_FILE)
+}
+
+// A missing or malformed file is treated as no high score yet.
+pub fn load_high_score(path: &Path) -> u32 {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|contents| contents.trim().parse().ok())
+    .unwrap_or(0)
+}
+
+pub fn save_high_score(path: &Path, score: u32) -> io::Result<()> {
+  fs::write(path, score.to_string())
+}
+
+// Same as the high score, a missing or malformed file means no records yet.
+pub fn load_records(path: &Path) -> Records {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|contents| ron::de::from_str(&contents).ok())
+    .unwrap_or_default()
+}
+
+pub fn save_records(path: &Path, records: &Records) -> io::Result<()> {
+  let contents =
+    ron::ser::to_string(records).map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+  fs::write(path, contents)
+}
+
+// Checked whenever a run ends or a life is lost, while `GameStats` still holds
+// the run's numbers. Like the high score, versus matches don't count.
+pub fn update_records(
+  play_mode: Res<PlayMode>,
+  stats: Res<GameStats>,
+  mut records: ResMut<Records>,
+  mut game_over_reader: EventReader<GameOverEvent>,
+  mut game_won_reader: EventReader<GameWonEvent>,
+) {
+  let game_over = game_over_reader.iter().last().is_some();
+  let game_won = game_won_reader.iter().last().is_some();
+  if *play_mode == PlayMode::Versus || (!game_over && !game_won) { return; }
+  let beaten = Records {
+    best_score: records.best_score.max(stats.score),
+    best_length: records.best_length.max(stats.length),
+    best_ticks: records.best_ticks.max(stats.ticks_survived),
+  };
+  if beaten == *records { return; }
+  *records = beaten;
+  if let Err(error) = save_records(&records_path(), &records) {
+    warn!("Couldn't save records: {}", error);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Unique per process and test, so tests running side by side don't share it.
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bevy_snake_{}_{}", std::process::id(), name))
+  }
+
+  #[test]
+  fn a_missing_high_score_file_is_zero() {
+    assert_eq!(load_high_score(&temp_path("missing_high_score")), 0);
+  }
+
+  #[test]
+  fn a_malformed_high_score_file_is_zero() {
+    let path = temp_path("malformed_high_score");
+    fs::write(&path, "abc").unwrap();
+    assert_eq!(load_high_score(&path), 0);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn a_saved_high_score_loads_back() {
+    let path = temp_path("saved_high_score");
+    save_high_score(&path, 42).unwrap();
+    assert_eq!(load_high_score(&path), 42);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn a_versus_match_leaves_the_records_alone() {
+    let mut world = World::default();
+    world.insert_resource(PlayMode::Versus);
+    let stats = GameStats { score: 30, length: 12, ticks_survived: 400, ..Default::default() };
+    world.insert_resource(stats);
+    world.insert_resource(Records::default());
+    world.insert_resource(Events::<GameOverEvent>::default());
+    world.insert_resource(Events::<GameWonEvent>::default());
+    world.get_resource_mut::<Events<GameOverEvent>>().unwrap().send(GameOverEvent);
+    let mut stage = SystemStage::single_threaded();
+    stage.add_system(update_records.system());
+    stage.run(&mut world);
+    assert_eq!(*world.get_resource::<Records>().unwrap(), Records::default());
+  }
+}
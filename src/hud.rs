@@ -0,0 +1,500 @@
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::prelude::*;
+
+use crate::achievements::{AchievementUnlocked, ACHIEVEMENTS};
+use crate::combo::Combo;
+use crate::debug::*;
+use crate::difficulty::Difficulty;
+use crate::game::{
+  board_size, tile_translation, ArenaSize, Countdown, GameState, Hunger, Lives, PlayMode, Player,
+  ScalingMode, Score, ScoreEvent, SurvivalTime, TimeAttack,
+};
+use crate::high_score::{HighScore, Records};
+use crate::power_ups::SlowMo;
+use crate::versus::{player_name, MatchState, PlayerScores, RoundResult};
+
+pub const TOAST_SECONDS: f32 = 3.0;
+pub const TOAST_SPACING: f32 = 30.0;
+pub const POPUP_LIFETIME: f32 = 0.5;
+pub const POPUP_RISE_SPEED: f32 = 60.0;
+
+pub struct ScoreText;
+
+pub struct StateText;
+
+pub struct TimeText;
+
+pub struct CountdownText;
+
+pub struct SlowMoText;
+
+// The filled part of the hunger bar, which shrinks as the snake gets hungrier.
+pub struct HungerBar;
+
+pub struct AchievementToast(pub Timer);
+
+pub struct ScorePopup(pub Timer);
+
+// What the window title starts with, before the live score.
+pub struct BaseTitle(pub String);
+
+impl Default for BaseTitle {
+  fn default() -> Self {
+    Self("Snake".to_string())
+  }
+}
+
+pub fn setup_hud(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  hunger: Option<Res<Hunger>>,
+  mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+  let font = asset_server.load("fonts/DejaVuSans.ttf");
+  commands.spawn_bundle(UiCameraBundle::default());
+  // Only there when hunger is on, along the top under the score.
+  if hunger.is_some() {
+    commands
+      .spawn_bundle(NodeBundle {
+        style: Style {
+          size: Size::new(Val::Px(200.0), Val::Px(8.0)),
+          position_type: PositionType::Absolute,
+          position: Rect {
+            top: Val::Px(36.0),
+            left: Val::Px(10.0),
+            ..Default::default()
+          },
+          ..Default::default()
+        },
+        material: color_materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
+        ..Default::default()
+      })
+      .with_children(|parent| {
+        parent
+          .spawn_bundle(NodeBundle {
+            style: Style {
+              size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+              ..Default::default()
+            },
+            material: color_materials.add(Color::rgb(0.9, 0.5, 0.1).into()),
+            ..Default::default()
+          })
+          .insert(HungerBar);
+      });
+  }
+  commands
+    .spawn_bundle(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          top: Val::Px(5.0),
+          left: Val::Px(10.0),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      text: Text::with_section(
+        "Score: 0  Best: 0",
+        TextStyle {
+          font: font.clone(),
+          font_size: 24.0,
+          color: Color::WHITE,
+        },
+        Default::default(),
+      ),
+      ..Default::default()
+    })
+    .insert(ScoreText);
+  commands
+    .spawn_bundle(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          top: Val::Px(5.0),
+          right: Val::Px(10.0),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      text: Text::with_section(
+        "",
+        TextStyle {
+          font: font.clone(),
+          font_size: 24.0,
+          color: Color::WHITE,
+        },
+        Default::default(),
+      ),
+      ..Default::default()
+    })
+    .insert(TimeText);
+  commands
+    .spawn_bundle(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          top: Val::Px(34.0),
+          right: Val::Px(10.0),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      text: Text::with_section(
+        "",
+        TextStyle {
+          font: font.clone(),
+          font_size: 20.0,
+          color: Color::rgb(0.4, 0.7, 1.0),
+        },
+        Default::default(),
+      ),
+      ..Default::default()
+    })
+    .insert(SlowMoText);
+  // In the middle of the play field rather than the UI, above all the tiles.
+  commands
+    .spawn_bundle(Text2dBundle {
+      text: Text::with_section(
+        "",
+        TextStyle {
+          font: font.clone(),
+          font_size: 96.0,
+          color: Color::WHITE,
+        },
+        TextAlignment {
+          vertical: VerticalAlign::Center,
+          horizontal: HorizontalAlign::Center,
+        },
+      ),
+      transform: Transform::from_xyz(0.0, 0.0, 2.0),
+      ..Default::default()
+    })
+    .insert(CountdownText);
+  commands
+    .spawn_bundle(TextBundle {
+      style: Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+          bottom: Val::Px(40.0),
+          left: Val::Px(10.0),
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      text: Text::with_section(
+        "",
+        TextStyle {
+          font,
+          font_size: 28.0,
+          color: Color::WHITE,
+        },
+        Default::default(),
+      ),
+      ..Default::default()
+    })
+    .insert(StateText);
+}
+
+// In versus each player's score is shown along with the round wins.
+pub fn score_text(
+  score: Res<Score>,
+  high_score: Res<HighScore>,
+  combo: Res<Combo>,
+  lives: Res<Lives>,
+  play_mode: Res<PlayMode>,
+  match_state: Res<MatchState>,
+  scores: Res<PlayerScores>,
+  mut texts: Query<&mut Text, With<ScoreText>>,
+) {
+  let changed =
+    score.is_changed() || high_score.is_changed() || combo.is_changed() || lives.is_changed();
+  if !changed && !match_state.is_changed() { return; }
+  let mut value = if *play_mode == PlayMode::Versus {
+    let mut value = format!(
+      "P1: {}  P2: {}  Round {} ({}-{})",
+      scores.get(Player::One),
+      scores.get(Player::Two),
+      match_state.round(),
+      match_state.p1_wins,
+      match_state.p2_wins,
+    );
+    match match_state.last_round {
+      Some(RoundResult::Won(player)) => {
+        value.push_str(&format!("  {} took the last round", player_name(player)));
+      }
+      Some(RoundResult::Draw) => value.push_str("  Draw, replaying the round"),
+      None => {}
+    }
+    value
+  } else {
+    format!("Score: {}  Best: {}  Lives: {}", score.0, high_score.0, lives.0)
+  };
+  if combo.multiplier > 1 {
+    value.push_str(&format!("  Combo: x{}", combo.multiplier));
+  }
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = value.clone();
+  }
+}
+
+pub fn state_text(
+  state: Res<State<GameState>>,
+  difficulty: Option<Res<Difficulty>>,
+  match_state: Res<MatchState>,
+  records: Res<Records>,
+  mut texts: Query<&mut Text, With<StateText>>,
+) {
+  let difficulty_changed = difficulty.as_ref().map_or(false, |difficulty| difficulty.is_changed());
+  if !state.is_changed() && !difficulty_changed { return; }
+  let message = match state.current() {
+    GameState::Menu => match difficulty {
+      Some(difficulty) => format!("Press Enter to start ({}, 1-3 to change)", difficulty.name()),
+      None => "Press Enter to start (1-3 to pick a difficulty)".to_string(),
+    },
+    GameState::Playing => "".to_string(),
+    GameState::Paused => "Paused - press Space to resume".to_string(),
+    GameState::Dying => "".to_string(),
+    GameState::GameOver => match match_state.winner {
+      Some(player) => format!(
+        "{} wins the match {}-{} - press Enter",
+        player_name(player),
+        match_state.p1_wins,
+        match_state.p2_wins,
+      ),
+      None => format!(
+        "Game Over - press Enter\nBest: {} points, length {}, {} ticks",
+        records.best_score,
+        records.best_length,
+        records.best_ticks,
+      ),
+    },
+    GameState::Won => "You win! - press Enter".to_string(),
+  };
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = message.clone();
+  }
+}
+
+// Counts down in time attack. In co-op the goal is to last as long as
+// possible, so it counts up instead.
+pub fn time_text(
+  survival_time: Res<SurvivalTime>,
+  time_attack: Option<Res<TimeAttack>>,
+  play_mode: Res<PlayMode>,
+  mut texts: Query<&mut Text, With<TimeText>>,
+) {
+  let seconds = match time_attack {
+    Some(time_attack) => {
+      let remaining = time_attack.limit.checked_sub(survival_time.0.elapsed()).unwrap_or_default();
+      remaining.as_secs_f32().ceil()
+    }
+    None if *play_mode == PlayMode::CoOp => survival_time.0.elapsed_secs().floor(),
+    None => return,
+  };
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = format!("Time: {}", seconds);
+  }
+}
+
+pub fn hunger_bar(hunger: Option<Res<Hunger>>, mut bars: Query<&mut Style, With<HungerBar>>) {
+  let hunger = match hunger {
+    Some(hunger) => hunger,
+    None => return,
+  };
+  if !hunger.is_changed() { return; }
+  for mut style in bars.iter_mut() {
+    style.size.width = Val::Percent(100.0 * (1.0 - hunger.0.percent()));
+  }
+}
+
+// Counts down the seconds left while slow-mo is on, blank otherwise.
+pub fn slow_mo_text(slow_mo: Res<SlowMo>, mut texts: Query<&mut Text, With<SlowMoText>>) {
+  if !slow_mo.is_changed() { return; }
+  let value = if slow_mo.0.finished() {
+    String::new()
+  } else {
+    let remaining = slow_mo.0.duration() - slow_mo.0.elapsed();
+    format!("Slow-mo: {}", remaining.as_secs_f32().ceil())
+  };
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = value.clone();
+  }
+}
+
+pub fn countdown_text(countdown: Res<Countdown>, mut texts: Query<&mut Text, With<CountdownText>>) {
+  if !countdown.is_changed() { return; }
+  let value = if countdown.0.finished() {
+    String::new()
+  } else {
+    let remaining = countdown.0.duration() - countdown.0.elapsed();
+    format!("{}", remaining.as_secs_f32().ceil())
+  };
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = value.clone();
+  }
+}
+
+// The title shows "Game Over" until the next run starts.
+pub fn window_title(
+  base_title: Res<BaseTitle>,
+  score: Res<Score>,
+  state: Res<State<GameState>>,
+  mut windows: ResMut<Windows>,
+) {
+  if !base_title.is_changed() && !score.is_changed() && !state.is_changed() { return; }
+  let window = match windows.get_primary_mut() {
+    Some(window) => window,
+    None => return,
+  };
+  let title = match state.current() {
+    GameState::GameOver => {
+      format!("{} \u{2014} Game Over \u{2014} Score: {}", base_title.0, score.0)
+    }
+    _ => format!("{} \u{2014} Score: {}", base_title.0, score.0),
+  };
+  window.set_title(title);
+}
+
+// Toasts stack downwards from the top of the window while several are showing.
+pub fn show_achievement_toasts(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  mut unlocked_reader: EventReader<AchievementUnlocked>,
+  toasts: Query<&AchievementToast>,
+) {
+  let mut count = toasts.iter().count();
+  for event in unlocked_reader.iter() {
+    let name = match ACHIEVEMENTS.iter().find(|achievement| achievement.id == event.id) {
+      Some(achievement) => achievement.name,
+      None => continue,
+    };
+    commands
+      .spawn_bundle(TextBundle {
+        style: Style {
+          position_type: PositionType::Absolute,
+          position: Rect {
+            top: Val::Px(60.0 + TOAST_SPACING * count as f32),
+            right: Val::Px(10.0),
+            ..Default::default()
+          },
+          ..Default::default()
+        },
+        text: Text::with_section(
+          format!("Achievement unlocked: {}", name),
+          TextStyle {
+            font: asset_server.load("fonts/DejaVuSans.ttf"),
+            font_size: 20.0,
+            color: Color::rgb(1.0, 0.85, 0.2),
+          },
+          Default::default(),
+        ),
+        ..Default::default()
+      })
+      .insert(AchievementToast(Timer::from_seconds(TOAST_SECONDS, false)));
+    count += 1;
+  }
+}
+
+pub fn fade_achievement_toasts(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut toasts: Query<(Entity, &mut AchievementToast, &mut Text)>,
+) {
+  for (entity, mut toast, mut text) in toasts.iter_mut() {
+    if toast.0.tick(time.delta()).finished() {
+      commands.entity(entity).despawn();
+      continue;
+    }
+    text.sections[0].style.color.set_a(1.0 - toast.0.percent());
+  }
+}
+
+pub fn spawn_score_popups(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  windows: Res<Windows>,
+  arena: Res<ArenaSize>,
+  scaling: Res<ScalingMode>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  let window = match windows.get_primary() {
+    Some(window) => window,
+    None => return,
+  };
+  let board = board_size(window, &arena, *scaling);
+  for event in score_reader.iter() {
+    let mut translation = tile_translation(&event.position, board, &arena);
+    translation.z = 1.0;
+    commands
+      .spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+          format!("{:+}", event.delta),
+          TextStyle {
+            font: asset_server.load("fonts/DejaVuSans.ttf"),
+            font_size: 20.0,
+            color: Color::WHITE,
+          },
+          TextAlignment {
+            vertical: VerticalAlign::Center,
+            horizontal: HorizontalAlign::Center,
+          },
+        ),
+        transform: Transform::from_translation(translation),
+        ..Default::default()
+      })
+      .insert(ScorePopup(Timer::from_seconds(POPUP_LIFETIME, false)));
+  }
+}
+
+pub fn animate_score_popups(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut popups: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+) {
+  for (entity, mut popup, mut transform, mut text) in popups.iter_mut() {
+    if popup.0.tick(time.delta()).finished() {
+      commands.entity(entity).despawn();
+      continue;
+    }
+    transform.translation.y += POPUP_RISE_SPEED * time.delta_seconds();
+    text.sections[0].style.color.set_a(1.0 - popup.0.percent());
+  }
+}
+
+// Everything on screen besides the board itself. The game runs the same
+// without it, so it can be left out of headless runs or swapped for another
+// HUD. Nothing here changes the game: it reads `Score`, `HighScore`, `Lives`,
+// `Combo`, `Records`, `MatchState`, `PlayerScores`, `PlayMode`, `Difficulty`,
+// `SurvivalTime`, `TimeAttack`, `Countdown`, `Hunger`, `SlowMo` and the game
+// state, the debug overlay also reads `SnakeSegments` and the board, and it
+// listens for `AchievementUnlocked` and `ScoreEvent`. The score is shown after the "scoring"
+// label, once the frame's points are in.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+  fn build(&self, app: &mut AppBuilder) {
+    app
+      .add_plugin(FrameTimeDiagnosticsPlugin::default())
+      .init_resource::<BaseTitle>()
+      .init_resource::<DebugOverlay>()
+      .add_startup_system(setup_hud.system())
+      .add_system(score_text.system().after("scoring"))
+      .add_system(state_text.system())
+      .add_system(time_text.system())
+      .add_system(countdown_text.system())
+      .add_system(slow_mo_text.system())
+      .add_system(hunger_bar.system())
+      .add_system(window_title.system())
+      .add_system(toggle_debug_overlay.system().label("toggle_debug_overlay"))
+      .add_system(
+        debug_overlay_visibility.system()
+          .label("debug_overlay_visibility")
+          .after("toggle_debug_overlay")
+      )
+      .add_system(debug_overlay_text.system().after("debug_overlay_visibility"))
+      .add_system(show_achievement_toasts.system())
+      .add_system(fade_achievement_toasts.system())
+      .add_system(spawn_score_popups.system())
+      .add_system(animate_score_popups.system());
+  }
+}
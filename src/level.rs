@@ -0,0 +1,199 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::game::{Direction, Position};
+
+// A level is an ASCII grid where every line is a row, top row first:
+// '#' is a wall, '.' is empty, 'S' is the snake's start and 'F' is food. A
+// digit is a portal, which takes the snake to the other tile with that digit.
+// 'H' and 'V' are obstacles that patrol horizontally and vertically.
+#[derive(Clone)]
+pub struct LevelData {
+  pub width: u32,
+  pub height: u32,
+  pub walls: Vec<Position>,
+  pub start: Position,
+  pub food: Vec<Position>,
+  pub portals: Vec<(u8, Position)>,
+  pub obstacles: Vec<(Position, Direction)>,
+}
+
+impl Default for LevelData {
+  fn default() -> Self {
+    Self {
+      width: 10,
+      height: 10,
+      walls: Vec::new(),
+      start: Position::new(3, 3),
+      food: Vec::new(),
+      portals: Vec::new(),
+      obstacles: Vec::new(),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+  Io(io::Error),
+  Empty,
+  NotRectangular { line: usize, expected: usize, found: usize },
+  UnknownTile { line: usize, column: usize, tile: char },
+  MissingStart,
+  MultipleStarts { line: usize, column: usize },
+  UnpairedPortal { id: u8, count: usize },
+}
+
+impl fmt::Display for LevelError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "couldn't read level: {}", error),
+      Self::Empty => write!(f, "level is empty"),
+      Self::NotRectangular { line, expected, found } => write!(
+        f,
+        "level isn't rectangular: line {} is {} tiles wide, expected {}",
+        line, found, expected,
+      ),
+      Self::UnknownTile { line, column, tile } => write!(
+        f,
+        "unknown tile '{}' at line {}, column {}",
+        tile, line, column,
+      ),
+      Self::MissingStart => write!(f, "level has no snake start ('S')"),
+      Self::MultipleStarts { line, column } => write!(
+        f,
+        "second snake start at line {}, column {}",
+        line, column,
+      ),
+      Self::UnpairedPortal { id, count } => write!(
+        f,
+        "portal '{}' appears {} times, expected exactly 2",
+        id, count,
+      ),
+    }
+  }
+}
+
+impl From<io::Error> for LevelError {
+  fn from(error: io::Error) -> Self {
+    Self::Io(error)
+  }
+}
+
+pub fn load_level(path: &str) -> Result<LevelData, LevelError> {
+  parse_level(&fs::read_to_string(path)?)
+}
+
+pub fn parse_level(contents: &str) -> Result<LevelData, LevelError> {
+  let mut rows = contents.lines().map(|line| line.trim_end()).collect::<Vec<&str>>();
+  while rows.last() == Some(&"") {
+    rows.pop();
+  }
+  let width = rows.first().ok_or(LevelError::Empty)?.chars().count();
+  let height = rows.len();
+
+  let mut walls = Vec::new();
+  let mut food = Vec::new();
+  let mut start = None;
+  let mut portals = Vec::new();
+  let mut obstacles = Vec::new();
+  for (row, line) in rows.iter().enumerate() {
+    let found = line.chars().count();
+    if found != width {
+      return Err(LevelError::NotRectangular { line: row + 1, expected: width, found });
+    }
+    // The first row of the file is the top of the arena.
+    let y = (height - 1 - row) as i32;
+    for (column, tile) in line.chars().enumerate() {
+      let position = Position::new(column as i32, y);
+      match tile {
+        '#' => walls.push(position),
+        'F' => food.push(position),
+        'S' if start.is_some() => {
+          return Err(LevelError::MultipleStarts { line: row + 1, column: column + 1 });
+        }
+        'S' => start = Some(position),
+        '0'..='9' => portals.push((tile as u8 - b'0', position)),
+        'H' => obstacles.push((position, Direction::Right)),
+        'V' => obstacles.push((position, Direction::Up)),
+        '.' => {}
+        _ => return Err(LevelError::UnknownTile { line: row + 1, column: column + 1, tile }),
+      }
+    }
+  }
+
+  for id in 0..=9 {
+    let count = portals.iter().filter(|&&(portal, _)| portal == id).count();
+    if count != 0 && count != 2 {
+      return Err(LevelError::UnpairedPortal { id, count });
+    }
+  }
+
+  Ok(LevelData {
+    width: width as u32,
+    height: height as u32,
+    walls,
+    start: start.ok_or(LevelError::MissingStart)?,
+    food,
+    portals,
+    obstacles,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn a_valid_level_is_read_top_row_first() {
+    let level = parse_level("#..F\n.S..\n...#\n").unwrap();
+    assert_eq!((level.width, level.height), (4, 3));
+    assert_eq!(level.walls, vec![Position::new(0, 2), Position::new(3, 0)]);
+    assert_eq!(level.food, vec![Position::new(3, 2)]);
+    assert_eq!(level.start, Position::new(1, 1));
+  }
+
+  #[test]
+  fn a_ragged_row_is_reported_with_its_line() {
+    match parse_level("S...\n...\n....") {
+      Err(LevelError::NotRectangular { line, expected, found }) => {
+        assert_eq!((line, expected, found), (2, 4, 3));
+      }
+      other => panic!("expected NotRectangular, got {:?}", other.err()),
+    }
+  }
+
+  #[test]
+  fn an_unknown_tile_is_reported_where_it_is() {
+    match parse_level("S.\n.x") {
+      Err(LevelError::UnknownTile { line, column, tile }) => {
+        assert_eq!((line, column, tile), (2, 2, 'x'));
+      }
+      other => panic!("expected UnknownTile, got {:?}", other.err()),
+    }
+  }
+
+  #[test]
+  fn a_level_needs_exactly_one_start() {
+    assert!(matches!(parse_level("..\n.."), Err(LevelError::MissingStart)));
+    assert!(matches!(
+      parse_level("S.\n.S"),
+      Err(LevelError::MultipleStarts { line: 2, column: 2 }),
+    ));
+  }
+
+  #[test]
+  fn portals_come_in_pairs() {
+    assert!(matches!(
+      parse_level("S1.\n11."),
+      Err(LevelError::UnpairedPortal { id: 1, count: 3 }),
+    ));
+    assert_eq!(parse_level("S1.\n.1.").unwrap().portals.len(), 2);
+  }
+
+  #[test]
+  fn trailing_blank_lines_are_ignored() {
+    let level = parse_level("S.\n..\n\n\n").unwrap();
+    assert_eq!((level.width, level.height), (2, 2));
+  }
+}
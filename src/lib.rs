@@ -0,0 +1,32 @@
+pub mod achievements;
+pub mod ai;
+pub mod assist;
+pub mod args;
+pub mod audio;
+pub mod camera;
+pub mod combo;
+pub mod debug;
+pub mod demo;
+pub mod difficulty;
+pub mod effects;
+pub mod food_arrow;
+pub mod food_strategy;
+pub mod game;
+pub mod gamepad;
+pub mod grid;
+pub mod high_score;
+pub mod hud;
+pub mod level;
+pub mod maze;
+pub mod obstacles;
+pub mod plugin;
+pub mod power_ups;
+pub mod replay;
+pub mod save;
+pub mod shrinking;
+pub mod theme;
+pub mod touch;
+pub mod versus;
+
+pub use hud::HudPlugin;
+pub use plugin::SnakePlugin;
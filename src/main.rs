@@ -1,70 +1,43 @@
 use bevy::prelude::*;
-use bevy::core::FixedTimestep;
 use bevy::render::pass::ClearColor;
 
-mod game;
+use bevy_snake::args::parse_args;
+use bevy_snake::hud::BaseTitle;
+use bevy_snake::level::{load_level, LevelData};
+use bevy_snake::{HudPlugin, SnakePlugin};
 
-use game::*;
+const LEVEL_PATH: &str = "assets/levels/classic.txt";
+const TITLE: &str = "Snaek..";
 
-fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
-  commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-  commands.insert_resource(Materials {
-    head_material: materials.add(Color::rgb(0.7, 0.7, 0.7).into()),
-    segment_material: materials.add(Color::rgb(0.3, 0.3, 0.3).into()),
-    food_material: materials.add(Color::rgb(1.0, 0.0, 1.0).into()),
+fn main() {
+  let args = parse_args();
+  let level = load_level(LEVEL_PATH).unwrap_or_else(|error| {
+    eprintln!("Couldn't load level {}: {}", LEVEL_PATH, error);
+    LevelData::default()
   });
-}
 
-fn main() {
+  let mut plugin = SnakePlugin::new();
+  if args.width.is_some() || args.height.is_some() {
+    plugin = plugin.with_arena_size(
+      args.width.unwrap_or(level.width),
+      args.height.unwrap_or(level.height),
+    );
+  }
+  if let Some(seed) = args.seed {
+    plugin = plugin.with_seed(seed);
+  }
+
   App::build()
     .insert_resource(WindowDescriptor {
-      title: "Snaek..".to_string(),
+      title: TITLE.to_string(),
       width: 500.0,
       height: 500.0,
       ..Default::default()
     })
-    .add_event::<GrowthEvent>()
-    .add_event::<GameOverEvent>()
     .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
-    .insert_resource(SnakeSegments::default())
-    .insert_resource(LastTailPosition::default())
-    .add_startup_system(setup.system())
-    .add_startup_stage("game_setup", SystemStage::single_threaded()
-      .with_system(spawn_snake.system().label("spawn_snake"))
-      .with_system(food_spawner.system().after("spawn_snake"))
-    )
-    .add_system(snake_movement_input.system().label(SnakeMovement::Input))
-    .add_system_set(
-      SystemSet::new()
-        .with_run_criteria(FixedTimestep::step(0.15))
-        .with_system(
-          snake_movement.system()
-            .label(SnakeMovement::Movement)
-            .after(SnakeMovement::Input)
-        )
-        .with_system(
-          snake_eating.system()
-            .label(SnakeMovement::Eating)
-            .after(SnakeMovement::Movement)
-        )
-        .with_system(
-          snake_growth.system()
-            .label(SnakeMovement::Growth)
-            .after(SnakeMovement::Eating)
-        )
-    )
-    .add_system(game_over.system().after(SnakeMovement::Movement))
-    .add_system_set(
-      SystemSet::new()
-        .with_run_criteria(FixedTimestep::step(3.0))
-        .with_system(food_spawner.system().after(SnakeMovement::Growth))
-    )
-    .add_system_set_to_stage(
-      CoreStage::PostUpdate,
-      SystemSet::new()
-        .with_system(position_translation.system())
-        .with_system(size_scaling.system()),
-    )
+    .insert_resource(BaseTitle(TITLE.to_string()))
     .add_plugins(DefaultPlugins)
+    .add_plugin(plugin.with_level(level))
+    .add_plugin(HudPlugin)
     .run();
 }
@@ -0,0 +1,180 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::ai::{ai_spawn_positions, AiOpponent};
+use crate::game::*;
+use crate::level::LevelData;
+
+// Whether every run gets a freshly generated maze in place of the level's own
+// walls and food.
+#[derive(Default)]
+pub struct MazeWalls(pub bool);
+
+pub struct Maze {
+  pub walls: Vec<Position>,
+  // Always reachable from the start.
+  pub food: Position,
+}
+
+const DIRECTIONS: [Direction; 4] =
+  [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+// Every tile reachable from `start` without crossing `walls`, `start` included.
+pub fn reachable(
+  start: Position,
+  walls: &HashSet<Position>,
+  arena: &ArenaSize,
+) -> HashSet<Position> {
+  let mut visited = HashSet::new();
+  let mut queue = VecDeque::new();
+  if !start.in_arena(arena) || walls.contains(&start) { return visited; }
+  visited.insert(start);
+  queue.push_back(start);
+  while let Some(position) = queue.pop_front() {
+    for &direction in DIRECTIONS.iter() {
+      let next = position + direction;
+      if next.in_arena(arena) && !walls.contains(&next) && visited.insert(next) {
+        queue.push_back(next);
+      }
+    }
+  }
+  visited
+}
+
+// A recursive backtracker carving corridors between the tiles two steps apart
+// from `start`, so every open tile connects to every other one. The tiles in
+// `clear` are kept open as well and joined to the nearest corridor. Returns
+// `None` if there's no open tile left for the food.
+pub fn generate_maze(
+  rng: &mut GameRng,
+  arena: &ArenaSize,
+  start: Position,
+  clear: &[Position],
+) -> Option<Maze> {
+  if !start.in_arena(arena) { return None; }
+  let is_cell = |position: Position| {
+    position.in_arena(arena) &&
+      (position.x() - start.x()) % 2 == 0 &&
+      (position.y() - start.y()) % 2 == 0
+  };
+  let mut open = HashSet::new();
+  let mut stack = vec![start];
+  open.insert(start);
+  while let Some(&cell) = stack.last() {
+    let unvisited = DIRECTIONS
+      .iter()
+      .copied()
+      .filter(|&direction| {
+        let next = cell + direction + direction;
+        is_cell(next) && !open.contains(&next)
+      })
+      .collect::<Vec<Direction>>();
+    match unvisited.choose(&mut rng.0) {
+      Some(&direction) => {
+        open.insert(cell + direction);
+        open.insert(cell + direction + direction);
+        stack.push(cell + direction + direction);
+      }
+      None => { stack.pop(); }
+    }
+  }
+  // A kept tile is either on a corridor already or next to one that lines up
+  // with the cells horizontally.
+  for &position in clear.iter().filter(|position| position.in_arena(arena)) {
+    open.insert(position);
+    if (position.x() - start.x()) % 2 != 0 {
+      let side = [Direction::Left, Direction::Right]
+        .iter()
+        .map(|&direction| position + direction)
+        .find(|side| side.in_arena(arena));
+      open.extend(side);
+    }
+  }
+  let walls = (0..arena.height as i32)
+    .flat_map(|y| (0..arena.width as i32).map(move |x| Position::new(x, y)))
+    .filter(|position| !open.contains(position))
+    .collect::<Vec<Position>>();
+  let wall_set = walls.iter().copied().collect::<HashSet<Position>>();
+  let mut candidates = reachable(start, &wall_set, arena)
+    .into_iter()
+    .filter(|position| *position != start && !clear.contains(position))
+    .collect::<Vec<Position>>();
+  // Sorted first so the same seed always picks the same tile.
+  candidates.sort_by_key(|position| (position.y(), position.x()));
+  let food = *candidates.choose(&mut rng.0)?;
+  Some(Maze { walls, food })
+}
+
+// Runs before anything is spawned from the level. The snakes' starting tiles
+// and the tile in front of them, the AI's start, portals and obstacles are all
+// kept clear.
+pub fn generate_maze_level(
+  maze_walls: Res<MazeWalls>,
+  arena: Res<ArenaSize>,
+  spawn: Res<SnakeSpawnConfig>,
+  play_mode: Res<PlayMode>,
+  ai_opponent: Res<AiOpponent>,
+  mut rng: ResMut<GameRng>,
+  mut level: ResMut<LevelData>,
+) {
+  if !maze_walls.0 { return; }
+  let mut spawns = vec![*spawn];
+  if play_mode.two_players() {
+    spawns.push(spawn.mirrored(&arena));
+  }
+  let mut clear = spawns
+    .iter()
+    .flat_map(|spawn| {
+      let mut positions = spawn.positions();
+      positions.push(spawn.start + spawn.direction);
+      positions
+    })
+    .collect::<Vec<Position>>();
+  clear.extend(level.portals.iter().map(|&(_, position)| position));
+  clear.extend(level.obstacles.iter().map(|&(position, _)| position));
+  // Kept clear, so `spawn_ai_snake` finds the same spot once the maze is in.
+  if ai_opponent.0 {
+    let taken = clear.iter().copied().collect::<HashSet<Position>>();
+    if let Some([head, tail]) = ai_spawn_positions(&arena, &taken) {
+      clear.extend([head, tail, head.neighbor(Direction::Down)].iter().copied());
+    }
+  }
+  match generate_maze(&mut rng, &arena, spawn.start, &clear) {
+    Some(maze) => {
+      level.walls = maze.walls;
+      level.food = vec![maze.food];
+    }
+    None => warn!("Couldn't fit a maze on the board, keeping the level's walls"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn every_open_tile_is_reachable_from_the_start() {
+    let clear = [Position::new(3, 3), Position::new(3, 4), Position::new(6, 1)];
+    for &(width, height) in [(10, 10), (9, 7), (4, 12)].iter() {
+      let arena = ArenaSize { width, height };
+      for seed in 0..20 {
+        let mut rng = GameRng::from_seed(seed);
+        let start = Position::new(1, 2);
+        let maze = generate_maze(&mut rng, &arena, start, &clear).unwrap();
+        let walls = maze.walls.iter().copied().collect::<HashSet<Position>>();
+        let connected = reachable(start, &walls, &arena);
+        let open = (0..height as i32)
+          .flat_map(|y| (0..width as i32).map(move |x| Position::new(x, y)))
+          .filter(|position| !walls.contains(position))
+          .collect::<Vec<Position>>();
+        for position in open {
+          let context = format!("{:?} in {}x{}, seed {}", position, width, height, seed);
+          assert!(connected.contains(&position), "{}", context);
+        }
+        assert!(connected.contains(&maze.food));
+      }
+    }
+  }
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::game::*;
+use crate::level::LevelData;
+
+// A wall that takes a step every movement tick, turning back whenever it runs
+// into something. Being a `Wall` as well, the snake dies on it and nothing is
+// spawned on top of it.
+pub struct MovingObstacle {
+  pub direction: Direction,
+}
+
+pub fn spawn_obstacles(mut commands: Commands, materials: Res<Materials>, level: Res<LevelData>) {
+  for &(position, direction) in level.obstacles.iter() {
+    commands
+      .spawn_bundle(SpriteBundle {
+        material: materials.wall_material.clone(),
+        ..Default::default()
+      })
+      .insert(Wall)
+      .insert(MovingObstacle { direction })
+      .insert(position)
+      .insert(PrevPosition::new(position))
+      .insert(Size::square(0.9));
+  }
+}
+
+// Obstacles move after the snakes. A head stepping onto an obstacle's tile dies
+// there even if the obstacle was about to move away, and an obstacle stepping
+// onto a head after it has moved kills it too, so a head and an obstacle can
+// never pass through each other. Obstacles bounce off the edges of the arena,
+// other walls, each other and anything else on the board, players' heads
+// excepted.
+pub fn obstacle_movement(
+  timer: Res<MovementTimer>,
+  arena: Res<ArenaSize>,
+  mut obstacles: Query<(&mut MovingObstacle, &mut Position)>,
+  heads: Query<(&Position, &Player), (With<SnakeHead>, Without<MovingObstacle>)>,
+  occupied: Query<&Position, (Occupying, Without<MovingObstacle>)>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+) {
+  if !timer.0.just_finished() { return; }
+  let heads = heads
+    .iter()
+    .map(|(&position, &player)| (position, player))
+    .collect::<HashMap<Position, Player>>();
+  let mut blocked = occupied
+    .iter()
+    .copied()
+    .filter(|position| !heads.contains_key(position))
+    .collect::<HashSet<Position>>();
+  blocked.extend(obstacles.iter_mut().map(|(_, position)| *position));
+  for (mut obstacle, mut position) in obstacles.iter_mut() {
+    let free = |direction: Direction| {
+      let next = *position + direction;
+      if next.in_arena(&arena) && !blocked.contains(&next) { Some(next) } else { None }
+    };
+    let next = match free(obstacle.direction) {
+      Some(next) => next,
+      None => {
+        obstacle.direction = obstacle.direction.opposite();
+        match free(obstacle.direction) {
+          Some(next) => next,
+          None => continue,
+        }
+      }
+    };
+    blocked.remove(&*position);
+    blocked.insert(next);
+    *position = next;
+    if let Some(&player) = heads.get(&next) {
+      crash_writer.send(SnakeCrashed { player });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  fn obstacle_world() -> World {
+    let mut world = World::default();
+    let step = Duration::from_secs_f64(SIMULATION_STEP);
+    let mut countdown = Timer::from_seconds(0.0, false);
+    countdown.tick(Duration::default());
+    world.insert_resource(MovementTimer(Timer::new(step, true)));
+    world.insert_resource(ArenaSize { width: 5, height: 5 });
+    world.insert_resource(EdgeConfig::default());
+    world.insert_resource(SnakeSegments::default());
+    world.insert_resource(LastTailPosition::default());
+    world.insert_resource(FatalBlockedPortals::default());
+    world.insert_resource(Countdown(countdown));
+    world.insert_resource(Invincible::default());
+    world.insert_resource(GameStats::default());
+    world.insert_resource(Events::<SnakeCrashed>::default());
+    world.insert_resource(Events::<DirectionChanged>::default());
+    world
+  }
+
+  // Player one's two segment snake, head first.
+  fn spawn_snake(world: &mut World, head: Position, tail: Position, direction: Direction) {
+    let head = world
+      .spawn()
+      .insert(head)
+      .insert(SnakeSegment)
+      .insert(Player::One)
+      .insert(SnakeHead::new(direction))
+      .id();
+    let tail = world.spawn().insert(tail).insert(SnakeSegment).insert(Player::One).id();
+    world.get_resource_mut::<SnakeSegments>().unwrap().insert(Player::One, vec![head, tail]);
+  }
+
+  fn spawn_obstacle(world: &mut World, position: Position, direction: Direction) -> Entity {
+    world.spawn().insert(position).insert(Wall).insert(MovingObstacle { direction }).id()
+  }
+
+  // The snakes move first, then the obstacles, like in the plugin.
+  fn step(world: &mut World) -> usize {
+    let mut stage = SystemStage::single_threaded();
+    stage
+      .add_system(snake_movement.system().label("movement"))
+      .add_system(obstacle_movement.system().after("movement"));
+    stage.run(world);
+    world.get_resource::<Events<SnakeCrashed>>().unwrap().iter_current_update_events().count()
+  }
+
+  #[test]
+  fn a_head_stepping_onto_a_leaving_obstacle_dies() {
+    let mut world = obstacle_world();
+    spawn_snake(&mut world, Position::new(1, 2), Position::new(0, 2), Direction::Right);
+    let obstacle = spawn_obstacle(&mut world, Position::new(2, 2), Direction::Right);
+    assert_eq!(step(&mut world), 1);
+    assert_eq!(world.get::<Position>(obstacle), Some(&Position::new(3, 2)));
+  }
+
+  #[test]
+  fn an_obstacle_stepping_onto_a_moved_head_kills_it() {
+    let mut world = obstacle_world();
+    spawn_snake(&mut world, Position::new(1, 2), Position::new(1, 1), Direction::Up);
+    let obstacle = spawn_obstacle(&mut world, Position::new(2, 3), Direction::Left);
+    assert_eq!(step(&mut world), 1);
+    assert_eq!(world.get::<Position>(obstacle), Some(&Position::new(1, 3)));
+  }
+
+  #[test]
+  fn an_obstacle_bounces_off_the_body() {
+    let mut world = obstacle_world();
+    spawn_snake(&mut world, Position::new(1, 3), Position::new(1, 2), Direction::Up);
+    let obstacle = spawn_obstacle(&mut world, Position::new(2, 3), Direction::Left);
+    assert_eq!(step(&mut world), 0);
+    assert_eq!(world.get::<Position>(obstacle), Some(&Position::new(3, 3)));
+    assert_eq!(world.get::<MovingObstacle>(obstacle).unwrap().direction, Direction::Right);
+  }
+}
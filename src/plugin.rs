@@ -0,0 +1,591 @@
+use std::time::Duration;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::render::pass::ClearColor;
+
+use crate::achievements::*;
+use crate::ai::*;
+use crate::assist::*;
+use crate::audio::*;
+use crate::camera::*;
+use crate::combo::*;
+use crate::demo::*;
+use crate::difficulty::*;
+use crate::effects::*;
+use crate::food_arrow::*;
+use crate::food_strategy::FoodPlacement;
+use crate::game::*;
+use crate::gamepad::*;
+use crate::grid::*;
+use crate::high_score::*;
+use crate::level::LevelData;
+use crate::maze::*;
+use crate::obstacles::*;
+use crate::power_ups::*;
+use crate::replay::*;
+use crate::save::*;
+use crate::shrinking::*;
+use crate::theme::*;
+use crate::touch::*;
+use crate::versus::*;
+
+pub const DEFAULT_WINDOW_SIZE: f32 = 500.0;
+
+// Registers the resources, events and systems of the game. Anything set through
+// the builder overrides the defaults; other resources inserted before the
+// plugin is added (e.g. `KeyBindings`) are left untouched.
+#[derive(Default)]
+pub struct SnakePlugin {
+  arena: Option<ArenaSize>,
+  level: Option<LevelData>,
+  materials: Option<MaterialsConfig>,
+  ai_opponent: bool,
+  play_mode: PlayMode,
+  match_length: Option<u32>,
+  seed: Option<u64>,
+  spawn: Option<SnakeSpawnConfig>,
+  time_attack: Option<Duration>,
+  replay: Option<Replay>,
+  record_replay: bool,
+  hunger: Option<Duration>,
+  shrinking_arena: Option<(Duration, u32)>,
+  difficulty: Option<Difficulty>,
+  maze: bool,
+  camera_follow: bool,
+  assist: bool,
+  animated_food: bool,
+  rainbow_food: bool,
+}
+
+impl SnakePlugin {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_arena_size(mut self, width: u32, height: u32) -> Self {
+    self.arena = Some(ArenaSize { width, height });
+    self
+  }
+
+  pub fn with_level(mut self, level: LevelData) -> Self {
+    self.level = Some(level);
+    self
+  }
+
+  pub fn with_materials(mut self, materials: MaterialsConfig) -> Self {
+    self.materials = Some(materials);
+    self
+  }
+
+  pub fn with_ai_opponent(mut self, enabled: bool) -> Self {
+    self.ai_opponent = enabled;
+    self
+  }
+
+  // Replaces the level's walls and food with a new random maze every run.
+  pub fn with_maze(mut self, enabled: bool) -> Self {
+    self.maze = enabled;
+    self
+  }
+
+  // Follows player one's head with the camera rather than showing the whole
+  // board, see `CameraZoom`.
+  pub fn with_camera_follow(mut self, enabled: bool) -> Self {
+    self.camera_follow = enabled;
+    self
+  }
+
+  pub fn with_play_mode(mut self, play_mode: PlayMode) -> Self {
+    self.play_mode = play_mode;
+    self
+  }
+
+  // Shades the moves that would box player one in, see `Assist`.
+  pub fn with_assist(mut self, enabled: bool) -> Self {
+    self.assist = enabled;
+    self
+  }
+
+  pub fn with_animated_food(mut self, enabled: bool) -> Self {
+    self.animated_food = enabled;
+    self
+  }
+
+  pub fn with_rainbow_food(mut self, enabled: bool) -> Self {
+    self.rainbow_food = enabled;
+    self
+  }
+
+  // Shorthand for co-op, see `PlayMode`.
+  pub fn with_two_players(mut self, enabled: bool) -> Self {
+    self.play_mode = if enabled { PlayMode::CoOp } else { PlayMode::Solo };
+    self
+  }
+
+  // Makes a versus match the best of `rounds`.
+  pub fn with_match_length(mut self, rounds: u32) -> Self {
+    self.match_length = Some(rounds.max(1));
+    self
+  }
+
+  pub fn with_spawn(mut self, spawn: SnakeSpawnConfig) -> Self {
+    self.spawn = Some(spawn);
+    self
+  }
+
+  // Closes the board in by `thickness` rings of walls every `interval`, see
+  // `ShrinkSchedule`.
+  pub fn with_shrinking_arena(mut self, interval: Duration, thickness: u32) -> Self {
+    self.shrinking_arena = Some((interval, thickness));
+    self
+  }
+
+  pub fn with_time_attack(mut self, limit: Duration) -> Self {
+    self.time_attack = Some(limit);
+    self
+  }
+
+  // The snake starves if it goes `limit` without eating, see `HungerPenalty`.
+  pub fn with_hunger(mut self, limit: Duration) -> Self {
+    self.hunger = Some(limit);
+    self
+  }
+
+  // Overrides the speed, food and poison settings with the preset's.
+  pub fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+    self.difficulty = Some(difficulty);
+    self
+  }
+
+  pub fn with_seed(mut self, seed: u64) -> Self {
+    self.seed = Some(seed);
+    self
+  }
+
+  // Plays `replay` back instead of taking player one's input from the keyboard.
+  pub fn with_replay(mut self, replay: Replay) -> Self {
+    self.replay = Some(replay);
+    self
+  }
+
+  // Records every run and saves it to `replay_path()` when it ends.
+  pub fn with_replay_recording(mut self, enabled: bool) -> Self {
+    self.record_replay = enabled;
+    self
+  }
+}
+
+impl Plugin for SnakePlugin {
+  fn build(&self, app: &mut AppBuilder) {
+    let level = self.level.clone().unwrap_or_default();
+    let arena = self.arena.unwrap_or(ArenaSize { width: level.width, height: level.height });
+    let spawn = self.spawn.unwrap_or(SnakeSpawnConfig { start: level.start, ..Default::default() });
+
+    // Only takes effect if the plugin is added before the window is created.
+    // Without a window at all, e.g. under `MinimalPlugins`, the systems that
+    // need one skip the frame.
+    if !app.world().contains_resource::<WindowDescriptor>() {
+      app.insert_resource(WindowDescriptor {
+        title: "Snake".to_string(),
+        width: DEFAULT_WINDOW_SIZE,
+        height: DEFAULT_WINDOW_SIZE,
+        ..Default::default()
+      });
+    }
+    app.init_resource::<Windows>();
+    // `apply_theme` needs one even without a renderer to clear the screen.
+    app.init_resource::<ClearColor>();
+    // A single `WallBehavior` inserted before the plugin still works, for every
+    // edge, as long as there's no `EdgeConfig` to say otherwise.
+    let wall_behavior = app.world().get_resource::<WallBehavior>().copied();
+    if let Some(wall_behavior) = wall_behavior {
+      if !app.world().contains_resource::<EdgeConfig>() {
+        app.insert_resource(EdgeConfig::all(wall_behavior));
+      }
+    }
+    match &self.materials {
+      Some(materials) => app.insert_resource(materials.clone()),
+      None => app.init_resource::<MaterialsConfig>(),
+    };
+    if let Some(limit) = self.time_attack {
+      app.insert_resource(TimeAttack { limit });
+    }
+    if let Some((interval, thickness)) = self.shrinking_arena {
+      app.insert_resource(ShrinkSchedule::new(interval, thickness));
+    }
+    if let Some(limit) = self.hunger {
+      app.insert_resource(Hunger(Timer::new(limit, false))).insert_resource(HungerLimit(limit));
+    }
+    if let Some(difficulty) = self.difficulty {
+      app.insert_resource(difficulty);
+    }
+    // A recording needs to know its seed, so it picks one if none was given.
+    let (replay_mode, replay) = match &self.replay {
+      Some(replay) => (ReplayMode::Playback, replay.clone()),
+      None if self.record_replay => {
+        (ReplayMode::Recording, Replay::new(self.seed.unwrap_or_else(rand::random)))
+      }
+      None => (ReplayMode::Off, Replay::default()),
+    };
+    app
+      .add_event::<GrowthEvent>()
+      .add_event::<GameOverEvent>()
+      .add_event::<GameWonEvent>()
+      .add_event::<LifeLostEvent>()
+      .add_event::<SnakeCrashed>()
+      .add_event::<RoundOver>()
+      .add_event::<ShrinkEvent>()
+      .add_event::<ScoreEvent>()
+      .add_event::<DirectionChanged>()
+      .add_event::<AchievementUnlocked>()
+      .insert_resource(SnakeSegments::default())
+      .insert_resource(LastTailPosition::default())
+      .init_resource::<KeyBindings>()
+      .init_resource::<AlternateKeyBindings>()
+      .init_resource::<ControlScheme>()
+      .init_resource::<AutoPause>()
+      .insert_resource(SwipeStarts::default())
+      .init_resource::<ShowTouchControls>()
+      .insert_resource(ConnectedGamepads::default())
+      .init_resource::<ActiveGamepad>()
+      .insert_resource(Score::default())
+      .init_resource::<StartingLives>()
+      .init_resource::<MinLength>()
+      .insert_resource(Lives::default())
+      .insert_resource(GameStats::default())
+      .insert_resource(SurvivalTime::default())
+      .insert_resource(MovementTimer::default())
+      .insert_resource(BaseInterval::default())
+      .insert_resource(SpawnTimer::default())
+      .insert_resource(SimulationClock::default())
+      .insert_resource(Countdown::default())
+      .insert_resource(DeathTimer::default())
+      .init_resource::<BoostKey>()
+      .insert_resource(Boosting::default())
+      .insert_resource(BoostDrain::default())
+      .insert_resource(self.seed.map(GameRng::from_seed).unwrap_or_default())
+      .insert_resource(self.seed.map(CosmeticRng::from_seed).unwrap_or_default())
+      .insert_resource(replay_mode)
+      .insert_resource(replay)
+      .init_resource::<SpeedConfig>()
+      .init_resource::<EdgeConfig>()
+      .init_resource::<VisualConfig>()
+      .init_resource::<ScalingMode>()
+      .init_resource::<FatalBlockedPortals>()
+      .init_resource::<FoodCount>()
+      .init_resource::<FoodPlacement>()
+      .init_resource::<FoodGrowth>()
+      .init_resource::<FoodLifetime>()
+      .init_resource::<RotTime>()
+      .init_resource::<HungerPenalty>()
+      .init_resource::<PoisonChance>()
+      .init_resource::<BonusChance>()
+      .init_resource::<BigFoodChance>()
+      .init_resource::<MagnetChance>()
+      .init_resource::<MagnetDuration>()
+      .insert_resource(MagnetTimer::default())
+      .init_resource::<GhostChance>()
+      .init_resource::<GhostConfig>()
+      .init_resource::<SlowMoChance>()
+      .init_resource::<SlowMoDuration>()
+      .insert_resource(SlowMo::default())
+      .insert_resource(Invincible::default())
+      .insert_resource(arena)
+      .insert_resource(level)
+      .insert_resource(spawn)
+      .init_resource::<GridVisible>()
+      .init_resource::<BorderThickness>()
+      .init_resource::<Muted>()
+      .init_resource::<ComboConfig>()
+      .insert_resource(Combo::default())
+      .insert_resource(MusicTimer::default())
+      .init_resource::<ScreenShake>()
+      .init_resource::<ParticleConfig>()
+      .insert_resource(AnimatedFood(self.animated_food))
+      .insert_resource(RainbowFood(self.rainbow_food))
+      .insert_resource(self.play_mode)
+      .insert_resource(self.match_length.map(MatchLength).unwrap_or_default())
+      .insert_resource(MatchState::default())
+      .insert_resource(PlayerScores::default())
+      .init_resource::<GradientSegments>()
+      .init_resource::<CurrentTheme>()
+      .insert_resource(GradientMaterials::default())
+      .insert_resource(AiOpponent(self.ai_opponent))
+      .insert_resource(MazeWalls(self.maze))
+      .insert_resource(CameraFollow(self.camera_follow))
+      .init_resource::<CameraZoom>()
+      .init_resource::<CameraFocus>()
+      .init_resource::<ShowFoodArrow>()
+      .insert_resource(Assist(self.assist))
+      .init_resource::<UnsafeMoves>()
+      .insert_resource(AiSnakeState::default())
+      .init_resource::<AiLookahead>()
+      .init_resource::<DemoTimer>()
+      .init_resource::<DemoState>()
+      .init_resource::<SavePath>()
+      .insert_resource(HighScore(load_high_score(&high_score_path())))
+      .insert_resource(load_records(&records_path()))
+      .insert_resource(load_achievements(&achievements_path()))
+      .add_startup_system(setup.system())
+      .add_startup_system(load_audio.system())
+      .add_startup_system(reset_lives.system())
+      // The D-pad is an input like the keyboard, so it stays with the game even
+      // when the HUD is swapped out.
+      .add_startup_system(spawn_touch_controls.system())
+      .add_startup_system_to_stage(StartupStage::PostStartup, spawn_grid.system())
+      .add_startup_system_to_stage(StartupStage::PostStartup, spawn_border.system())
+      .add_startup_system_to_stage(StartupStage::PostStartup, spawn_food_arrow.system())
+      .add_startup_system_to_stage(StartupStage::PostStartup, spawn_assist_overlays.system())
+      .add_state(GameState::Menu)
+      .add_system_to_stage(
+        CoreStage::PreUpdate,
+        quick_load.system().with_run_criteria(run_while_playing.system()).after(InputSystem)
+      )
+      .add_system(start_game.system())
+      .add_system(toggle_pause.system())
+      .add_system(auto_pause.system())
+      .add_system(exit_on_escape.system())
+      .add_system(gamepad_connections.system())
+      // The AI plays on the title screen until a game is started.
+      .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(spawn_demo.system()))
+      .add_system_set(SystemSet::on_update(GameState::Menu).with_system(demo_movement.system()))
+      .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(despawn_demo.system()))
+      .add_system_set(
+        SystemSet::on_enter(GameState::Playing)
+          .with_system(clear_board.system().label("clear_board"))
+          .with_system(start_countdown.system())
+          .with_system(reset_lives.system())
+          .with_system(reset_boost.system())
+          .with_system(reset_power_ups.system())
+          .with_system(reset_hunger.system())
+          .with_system(reset_match.system())
+          .with_system(reset_shrink_schedule.system())
+          .with_system(reset_spawn_timer.system())
+          .with_system(start_replay.system().label("start_replay"))
+          .with_system(generate_maze_level.system().label("generate_maze").after("start_replay"))
+          .with_system(spawn_walls.system().after("clear_board").after("generate_maze"))
+          .with_system(spawn_portals.system().after("clear_board"))
+          .with_system(spawn_obstacles.system().after("clear_board"))
+          .with_system(
+            spawn_snake.system()
+              .label("spawn_snake")
+              .after("clear_board")
+              .after("generate_maze")
+          )
+          .with_system(spawn_ai_snake.system().label("spawn_ai_snake").after("clear_board"))
+          .with_system(
+            spawn_initial_food.system()
+              .after("spawn_snake")
+              .after("spawn_ai_snake")
+              .after("start_replay")
+              .after("generate_maze")
+          )
+      )
+      .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+          .with_system(countdown.system().label("countdown"))
+          .with_system(snake_movement_input.system().label(SnakeMovement::Input))
+          .with_system(swipe_input.system().label(SnakeMovement::Input))
+          .with_system(dpad_input.system().label(SnakeMovement::Input))
+          .with_system(gamepad_input.system().label(SnakeMovement::Input))
+          .with_system(snake_shrink.system().after(SnakeMovement::Eating))
+          .with_system(quick_save.system())
+          .with_system(
+            update_game_stats.system()
+              .label("update_game_stats")
+              .after(SnakeMovement::Growth)
+          )
+          .with_system(
+            check_achievements.system()
+              .after("update_game_stats")
+              .after("survival_timer")
+          )
+      )
+      // The simulation steps at a fixed rate, see `run_simulation`. Everything
+      // that moves things on the board, draws from `GameRng` or counts down to
+      // something that does lives here, so a seeded run or a replay plays out
+      // the same however fast it's drawn.
+      .add_system_set(
+        SystemSet::new()
+          .with_run_criteria(run_simulation.system())
+          .with_system(
+            boost.system()
+              .label("boost")
+              .after(SnakeMovement::Input)
+              .before(SnakeMovement::Movement)
+          )
+          .with_system(invincibility.system().before(SnakeMovement::Movement))
+          .with_system(survival_timer.system().label("survival_timer").after("countdown"))
+          .with_system(time_attack.system().after("survival_timer"))
+          .with_system(
+            shrink_arena.system().after("countdown").after(SnakeMovement::Movement)
+          )
+          .with_system(hunger.system().after(SnakeMovement::Eating).after("countdown"))
+          .with_system(collect_magnets.system().after(SnakeMovement::Movement))
+          .with_system(collect_ghosts.system().after(SnakeMovement::Movement))
+          .with_system(collect_slow_mo.system().after(SnakeMovement::Movement))
+          .with_system(combo_scoring.system().label("combo").after(SnakeMovement::Eating))
+          .with_system(scoring.system().label("scoring").after("combo"))
+          .with_system(player_scoring.system().label("scoring").after("combo"))
+          .with_system(increase_speed.system().after(SnakeMovement::Growth))
+          .with_system(bonus_food_expiry.system().after(SnakeMovement::Eating))
+          .with_system(food_expiry.system().after(SnakeMovement::Eating))
+          .with_system(food_rotting.system().after(SnakeMovement::Eating))
+          .with_system(
+            food_spawner.system()
+              .label("food_spawner")
+              .after(SnakeMovement::Growth)
+              .after("ai_eating")
+          )
+          .with_system(spawn_timer.system().label("spawn_timer").after("countdown"))
+          // One after the other, so they always draw from `GameRng` in order.
+          .with_system(
+            poison_spawner.system()
+              .label("poison_spawner")
+              .after("food_spawner")
+              .after("spawn_timer")
+          )
+          .with_system(
+            bonus_food_spawner.system().label("bonus_food_spawner").after("poison_spawner")
+          )
+          .with_system(
+            big_food_spawner.system().label("big_food_spawner").after("bonus_food_spawner")
+          )
+          .with_system(magnet_spawner.system().label("magnet_spawner").after("big_food_spawner"))
+          .with_system(ghost_spawner.system().label("ghost_spawner").after("magnet_spawner"))
+          .with_system(slow_mo_spawner.system().after("ghost_spawner"))
+          .with_system(
+            prepare_replay_input.system()
+              .after(SnakeMovement::Input)
+              .before(SnakeMovement::Movement)
+          )
+          .with_system(
+            play_replay.system()
+              .after(SnakeMovement::Input)
+              .before(SnakeMovement::Movement)
+          )
+          .with_system(
+            snake_movement.system()
+              .label(SnakeMovement::Movement)
+              .after(SnakeMovement::Input)
+              .after("countdown")
+          )
+          .with_system(record_replay.system().after(SnakeMovement::Movement))
+          .with_system(
+            obstacle_movement.system()
+              .label("obstacle_movement")
+              .after(SnakeMovement::Movement)
+              .after("ai_movement")
+              .before(SnakeMovement::Eating)
+          )
+          .with_system(
+            snake_eating.system()
+              .label(SnakeMovement::Eating)
+              .after(SnakeMovement::Movement)
+          )
+          .with_system(
+            snake_growth.system()
+              .label(SnakeMovement::Growth)
+              .after(SnakeMovement::Eating)
+          )
+          .with_system(slow_mo.system().label("slow_mo").before(SnakeMovement::Movement))
+          .with_system(
+            movement_interval.system()
+              .after("boost")
+              .after("slow_mo")
+              .before(SnakeMovement::Movement)
+          )
+          .with_system(
+            magnet_pull.system()
+              .after(SnakeMovement::Movement)
+              .before(SnakeMovement::Eating)
+          )
+          .with_system(ai_steering.system().label("ai_steering").after(SnakeMovement::Movement))
+          .with_system(ai_movement.system().label("ai_movement").after("ai_steering"))
+          .with_system(
+            ai_eating.system()
+              .label("ai_eating")
+              .after("ai_movement")
+              .after(SnakeMovement::Eating)
+          )
+          .with_system(
+            unsafe_moves.system()
+              .after(SnakeMovement::Growth)
+              .after("ai_eating")
+              .after("obstacle_movement")
+          )
+          .with_system(
+            track_previous_positions.system()
+              .after(SnakeMovement::Growth)
+              .after("ai_eating")
+              .after("obstacle_movement")
+          )
+      )
+      .add_system(
+        snake_crashes.system()
+          .label("snake_crashes")
+          .after(SnakeMovement::Movement)
+          .after("obstacle_movement")
+      )
+      .add_system(
+        versus_rounds.system()
+          .label("versus_rounds")
+          .after(SnakeMovement::Movement)
+          .after("obstacle_movement")
+      )
+      .add_system(
+        game_over.system()
+          .label("game_over")
+          .after("snake_crashes")
+          .after("versus_rounds")
+      )
+      .add_system_set(
+        SystemSet::on_enter(GameState::GameOver).with_system(save_recorded_replay.system())
+      )
+      .add_system_set(
+        SystemSet::on_enter(GameState::Won).with_system(save_recorded_replay.system())
+      )
+      .add_system_set(SystemSet::on_enter(GameState::Dying).with_system(start_dying.system()))
+      .add_system_set(SystemSet::on_update(GameState::Dying).with_system(dying.system()))
+      .add_system(respawn_snake.system().after("game_over").after("versus_rounds"))
+      // Before `game_won`, which clears the stats the records are taken from.
+      .add_system(update_records.system().label("update_records"))
+      .add_system(game_won.system().after("update_records"))
+      .add_system(reset_combo.system())
+      .add_system(choose_difficulty.system())
+      .add_system(apply_difficulty.system())
+      .add_system(spawn_particles.system())
+      .add_system(animate_particles.system())
+      .add_system(animate_food.system())
+      .add_system(rainbow_food.system())
+      .add_system(start_screen_shake.system().label("start_screen_shake"))
+      .add_system(screen_shake.system().after("start_screen_shake"))
+      .add_system(camera_zoom.system())
+      .add_system(eat_sound.system())
+      .add_system(death_sound.system())
+      .add_system(toggle_mute.exclusive_system())
+      .add_system(music_length.system().label("music_length"))
+      .add_system(background_music.system().after("music_length"))
+      .add_system(cycle_theme.system().label("cycle_theme"))
+      .add_system(apply_theme.system().label("apply_theme").after("cycle_theme"))
+      .add_system(segment_gradient.system().after("apply_theme"))
+      .add_system(toggle_grid.system())
+      .add_system(grid_visibility.system())
+      .add_system_set_to_stage(
+        CoreStage::PostUpdate,
+        SystemSet::new()
+          .with_system(reanchor_to_arena.system().before("position_translation"))
+          .with_system(position_translation.system().label("position_translation"))
+          .with_system(size_scaling.system())
+          .with_system(head_rotation.system())
+          .with_system(segment_shapes.system())
+          .with_system(rebuild_grid.system())
+          .with_system(grid_layout.system())
+          .with_system(border_layout.system())
+          .with_system(camera_follow.system().after("position_translation"))
+          .with_system(food_arrow.system().after("position_translation"))
+          .with_system(assist_overlays.system().after("position_translation")),
+      );
+  }
+}
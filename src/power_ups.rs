@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::game::*;
+
+pub struct MagnetPickup;
+
+// Chance of a magnet being spawned every time the food spawner runs.
+pub struct MagnetChance(pub f32);
+
+impl Default for MagnetChance {
+  fn default() -> Self {
+    Self(0.05)
+  }
+}
+
+// Seconds a magnet keeps pulling food for.
+pub struct MagnetDuration(pub f32);
+
+impl Default for MagnetDuration {
+  fn default() -> Self {
+    Self(5.0)
+  }
+}
+
+// The magnet is on while this is running. It starts out finished.
+pub struct MagnetTimer(pub Timer);
+
+impl Default for MagnetTimer {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(0.0, false);
+    timer.tick(Default::default());
+    Self(timer)
+  }
+}
+
+pub struct GhostPickup;
+
+// Chance of a ghost being spawned every time the food spawner runs.
+pub struct GhostChance(pub f32);
+
+impl Default for GhostChance {
+  fn default() -> Self {
+    Self(0.05)
+  }
+}
+
+// How long a ghost pickup makes the player invincible for, and how quickly the
+// head flashes meanwhile, both in seconds.
+pub struct GhostConfig {
+  pub duration: f32,
+  pub flash_interval: f32,
+}
+
+impl Default for GhostConfig {
+  fn default() -> Self {
+    Self {
+      duration: 5.0,
+      flash_interval: 0.1,
+    }
+  }
+}
+
+pub struct SlowMoPickup;
+
+// How many times longer the movement interval is while slow-mo is on.
+pub const SLOW_MO_FACTOR: u32 = 2;
+
+// Chance of a slow-mo pickup being spawned every time the food spawner runs.
+pub struct SlowMoChance(pub f32);
+
+impl Default for SlowMoChance {
+  fn default() -> Self {
+    Self(0.05)
+  }
+}
+
+// Seconds slow-mo lasts for.
+pub struct SlowMoDuration(pub f32);
+
+impl Default for SlowMoDuration {
+  fn default() -> Self {
+    Self(5.0)
+  }
+}
+
+// Slow-mo is on while this is running. It starts out finished.
+pub struct SlowMo(pub Timer);
+
+impl Default for SlowMo {
+  fn default() -> Self {
+    let mut timer = Timer::from_seconds(0.0, false);
+    timer.tick(Default::default());
+    Self(timer)
+  }
+}
+
+fn spawn_power_up(
+  commands: &mut Commands,
+  material: Handle<ColorMaterial>,
+  position: Position,
+) -> Entity {
+  commands
+    .spawn_bundle(SpriteBundle { material, ..Default::default() })
+    .insert(PowerUp)
+    .insert(position)
+    .insert(Size::square(0.6))
+    .id()
+}
+
+// Only one magnet is on the board at a time.
+pub fn magnet_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<MagnetChance>,
+  magnets: Query<Entity, With<MagnetPickup>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if magnets.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  if let Some(position) = random_free_position(&mut rng, &arena, &taken_positions) {
+    let magnet = spawn_power_up(&mut commands, materials.magnet_material.clone(), position);
+    commands.entity(magnet).insert(MagnetPickup);
+  }
+}
+
+pub fn collect_magnets(
+  mut commands: Commands,
+  duration: Res<MagnetDuration>,
+  mut magnet_timer: ResMut<MagnetTimer>,
+  magnets: Query<(Entity, &Position), With<MagnetPickup>>,
+  heads: Query<&Position, (With<SnakeHead>, With<Player>)>,
+) {
+  for (magnet, magnet_position) in magnets.iter() {
+    if heads.iter().any(|head_position| head_position == magnet_position) {
+      commands.entity(magnet).despawn();
+      magnet_timer.0 = Timer::from_seconds(duration.0, false);
+    }
+  }
+}
+
+// While the magnet is on, the food closest to player one's head takes a step
+// towards it every movement tick, along whichever axis it's further away on.
+// It won't step off the board, into the body or onto other food, but it can
+// step onto the head to be eaten straight away.
+pub fn magnet_pull(
+  movement_timer: Res<MovementTimer>,
+  arena: Res<ArenaSize>,
+  mut magnet_timer: ResMut<MagnetTimer>,
+  heads: Query<(&Position, &Player), (With<SnakeHead>, Without<Food>)>,
+  body: Query<&Position, (With<SnakeSegment>, Without<SnakeHead>, Without<Food>)>,
+  mut food: Query<&mut Position, With<Food>>,
+) {
+  let step = Duration::from_secs_f64(SIMULATION_STEP);
+  if magnet_timer.0.tick(step).finished() || !movement_timer.0.just_finished() { return; }
+  let head = match heads.iter().find(|(_, player)| **player == Player::One) {
+    Some((&head, _)) => head,
+    None => return,
+  };
+  let distance =
+    |position: &Position| (head.x() - position.x()).abs() + (head.y() - position.y()).abs();
+  let mut blocked = body.iter().copied().collect::<HashSet<Position>>();
+  blocked.extend(food.iter_mut().map(|position| *position));
+  let mut nearest = match food.iter_mut().min_by_key(|position| distance(position)) {
+    Some(nearest) => nearest,
+    None => return,
+  };
+  let dx = head.x() - nearest.x();
+  let dy = head.y() - nearest.y();
+  let next = if dx.abs() >= dy.abs() {
+    Position::new(nearest.x() + dx.signum(), nearest.y())
+  } else {
+    Position::new(nearest.x(), nearest.y() + dy.signum())
+  };
+  if next.in_arena(&arena) && !blocked.contains(&next) {
+    *nearest = next;
+  }
+}
+
+// Only one ghost is on the board at a time.
+pub fn ghost_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<GhostChance>,
+  ghosts: Query<Entity, With<GhostPickup>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if ghosts.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  if let Some(position) = random_free_position(&mut rng, &arena, &taken_positions) {
+    let ghost = spawn_power_up(&mut commands, materials.ghost_material.clone(), position);
+    commands.entity(ghost).insert(GhostPickup);
+  }
+}
+
+pub fn collect_ghosts(
+  mut commands: Commands,
+  config: Res<GhostConfig>,
+  mut invincible: ResMut<Invincible>,
+  ghosts: Query<(Entity, &Position), With<GhostPickup>>,
+  heads: Query<&Position, (With<SnakeHead>, With<Player>)>,
+) {
+  for (ghost, ghost_position) in ghosts.iter() {
+    if heads.iter().any(|head_position| head_position == ghost_position) {
+      commands.entity(ghost).despawn();
+      invincible.0 = Timer::from_seconds(config.duration, false);
+    }
+  }
+}
+
+// Only one slow-mo pickup is on the board at a time.
+pub fn slow_mo_spawner(
+  mut commands: Commands,
+  materials: Res<Materials>,
+  arena: Res<ArenaSize>,
+  spawn_timer: Res<SpawnTimer>,
+  mut rng: ResMut<GameRng>,
+  chance: Res<SlowMoChance>,
+  pickups: Query<Entity, With<SlowMoPickup>>,
+  occupied: Query<&Position, Occupying>,
+) {
+  if !spawn_timer.0.just_finished() { return; }
+  if pickups.iter().next().is_some() || rng.0.gen::<f32>() >= chance.0 { return; }
+  let taken_positions = occupied.iter().copied().collect::<HashSet<Position>>();
+  if let Some(position) = random_free_position(&mut rng, &arena, &taken_positions) {
+    let pickup = spawn_power_up(&mut commands, materials.slow_mo_material.clone(), position);
+    commands.entity(pickup).insert(SlowMoPickup);
+  }
+}
+
+// Picking one up while slow-mo is already on only makes it last longer.
+pub fn collect_slow_mo(
+  mut commands: Commands,
+  duration: Res<SlowMoDuration>,
+  mut slow_mo: ResMut<SlowMo>,
+  pickups: Query<(Entity, &Position), With<SlowMoPickup>>,
+  heads: Query<&Position, (With<SnakeHead>, With<Player>)>,
+) {
+  for (pickup, pickup_position) in pickups.iter() {
+    if !heads.iter().any(|head_position| head_position == pickup_position) { continue; }
+    commands.entity(pickup).despawn();
+    slow_mo.0 = Timer::from_seconds(duration.0, false);
+  }
+}
+
+// Steps with the simulation, so slow-mo ends on the same tick in a replay.
+// `movement_interval` does the actual slowing down for as long as it lasts.
+pub fn slow_mo(mut slow_mo: ResMut<SlowMo>) {
+  if slow_mo.0.finished() { return; }
+  slow_mo.0.tick(Duration::from_secs_f64(SIMULATION_STEP));
+}
+
+// Runs every simulation step, so the snake is a ghost for the same number of
+// moves however fast the game is drawn.
+pub fn invincibility(
+  config: Res<GhostConfig>,
+  mut invincible: ResMut<Invincible>,
+  mut heads: Query<&mut Visible, (With<SnakeHead>, With<Player>)>,
+) {
+  invincible.0.tick(Duration::from_secs_f64(SIMULATION_STEP));
+  let flashes = (invincible.0.elapsed_secs() / config.flash_interval) as u32;
+  let is_visible = invincible.0.finished() || flashes % 2 == 0;
+  for mut visible in heads.iter_mut() {
+    visible.is_visible = is_visible;
+  }
+}
+
+// A new run starts without any power-up active. The movement interval has
+// already been reset when the last run ended, so slow-mo is simply dropped.
+pub fn reset_power_ups(
+  mut magnet_timer: ResMut<MagnetTimer>,
+  mut invincible: ResMut<Invincible>,
+  mut slow_mo: ResMut<SlowMo>,
+) {
+  *magnet_timer = MagnetTimer::default();
+  *invincible = Invincible::default();
+  *slow_mo = SlowMo::default();
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::effects::CosmeticRng;
+use crate::game::*;
+
+pub const REPLAY_FILE: &str = "replay.ron";
+
+// The direction player one was steered in on every movement tick of a run, the
+// simulation steps boosting started or stopped on, and the seed the run's
+// `GameRng` started from. Everything else that decides how a run goes is run
+// off the simulation clock and that seed, so playing these back reproduces the
+// run exactly.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Replay {
+  pub seed: u64,
+  pub inputs: Vec<Direction>,
+  #[serde(default)]
+  pub boost_toggles: Vec<u64>,
+  #[serde(skip)]
+  pending: Option<Direction>,
+}
+
+impl Replay {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, ..Default::default() }
+  }
+
+  // Whether player one was boosting on the given simulation step.
+  pub fn boosting_at(&self, step: u64) -> bool {
+    self.boost_toggles.iter().filter(|&&toggle| toggle <= step).count() % 2 == 1
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+  Off,
+  Recording,
+  Playback,
+}
+
+impl Default for ReplayMode {
+  fn default() -> Self {
+    Self::Off
+  }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+  Io(io::Error),
+  Format(ron::Error),
+}
+
+impl fmt::Display for ReplayError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "couldn't read replay: {}", error),
+      Self::Format(error) => write!(f, "malformed replay: {}", error),
+    }
+  }
+}
+
+impl From<io::Error> for ReplayError {
+  fn from(error: io::Error) -> Self {
+    Self::Io(error)
+  }
+}
+
+impl From<ron::Error> for ReplayError {
+  fn from(error: ron::Error) -> Self {
+    Self::Format(error)
+  }
+}
+
+pub fn replay_path() -> PathBuf {
+  std::env::current_exe()
+    .ok()
+    .and_then(|exe| exe.parent().map(|dir| dir.join(REPLAY_FILE)))
+    .unwrap_or_else(|| PathBuf::from(REPLAY_FILE))
+}
+
+pub fn save_replay(path: &Path, replay: &Replay) -> Result<(), ReplayError> {
+  let contents = ron::ser::to_string_pretty(replay, ron::ser::PrettyConfig::default())?;
+  fs::write(path, contents)?;
+  Ok(())
+}
+
+pub fn load_replay(path: &Path) -> Result<Replay, ReplayError> {
+  Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Every run of a recording or a playback starts from the replay's seed.
+pub fn start_replay(
+  mode: Res<ReplayMode>,
+  mut replay: ResMut<Replay>,
+  mut rng: ResMut<GameRng>,
+  mut cosmetic_rng: ResMut<CosmeticRng>,
+) {
+  match *mode {
+    ReplayMode::Off => return,
+    ReplayMode::Recording => {
+      replay.inputs.clear();
+      replay.boost_toggles.clear();
+      replay.pending = None;
+    }
+    ReplayMode::Playback => {}
+  }
+  *rng = GameRng::from_seed(replay.seed);
+  *cosmetic_rng = CosmeticRng::from_seed(replay.seed);
+}
+
+// The direction player one is about to move in, taken before `snake_movement`
+// runs. Bouncing off a wall changes the direction the snake ends up moving in,
+// so it's the input that's recorded rather than the result.
+pub fn prepare_replay_input(
+  mode: Res<ReplayMode>,
+  mut replay: ResMut<Replay>,
+  heads: Query<(&SnakeHead, &Player)>,
+) {
+  if *mode != ReplayMode::Recording { return; }
+  if let Some((head, _)) = heads.iter().find(|(_, player)| **player == Player::One) {
+    replay.pending = Some(head.input_queue.front().copied().unwrap_or(head.input_direction));
+  }
+}
+
+// Runs after `snake_movement`, so every tick it counted gets an input.
+pub fn record_replay(mode: Res<ReplayMode>, stats: Res<GameStats>, mut replay: ResMut<Replay>) {
+  if *mode != ReplayMode::Recording { return; }
+  if stats.ticks_survived as usize <= replay.inputs.len() { return; }
+  if let Some(direction) = replay.pending.take() {
+    replay.inputs.push(direction);
+  }
+}
+
+// Runs before `snake_movement` and hands it the recorded direction for the
+// tick it's about to count, in place of anything queued from the keyboard.
+pub fn play_replay(
+  mode: Res<ReplayMode>,
+  stats: Res<GameStats>,
+  replay: Res<Replay>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+) {
+  if *mode != ReplayMode::Playback { return; }
+  let direction = match replay.inputs.get(stats.ticks_survived as usize) {
+    Some(&direction) => direction,
+    None => return,
+  };
+  for (mut head, player) in heads.iter_mut() {
+    if *player == Player::One {
+      head.input_queue.clear();
+      head.input_direction = direction;
+    }
+  }
+}
+
+pub fn save_recorded_replay(mode: Res<ReplayMode>, replay: Res<Replay>) {
+  if *mode != ReplayMode::Recording { return; }
+  if let Err(error) = save_replay(&replay_path(), &replay) {
+    warn!("Couldn't save replay: {}", error);
+  }
+}
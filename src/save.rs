@@ -0,0 +1,293 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::*;
+use crate::power_ups::SlowMo;
+
+pub const SAVE_FILE: &str = "quicksave.ron";
+pub const QUICK_SAVE_KEY: KeyCode = KeyCode::F5;
+pub const QUICK_LOAD_KEY: KeyCode = KeyCode::F9;
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedSnake {
+  pub player: Player,
+  // Head first, the same order as `SnakeSegments`.
+  pub segments: Vec<Position>,
+  pub direction: Direction,
+}
+
+// Everything needed to pick a run back up. `GameRng` can't be saved as it is,
+// so the save keeps a seed drawn from a copy of it, which leaves the running
+// game's stream alone. A load reseeds from it.
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+  pub snakes: Vec<SavedSnake>,
+  pub food: Vec<Position>,
+  pub score: u32,
+  pub lives: u32,
+  pub movement_interval: f32,
+  pub rng_seed: u64,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+  Io(io::Error),
+  Format(ron::Error),
+}
+
+impl fmt::Display for SaveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "couldn't access save: {}", error),
+      Self::Format(error) => write!(f, "malformed save: {}", error),
+    }
+  }
+}
+
+impl From<io::Error> for SaveError {
+  fn from(error: io::Error) -> Self {
+    Self::Io(error)
+  }
+}
+
+impl From<ron::Error> for SaveError {
+  fn from(error: ron::Error) -> Self {
+    Self::Format(error)
+  }
+}
+
+pub fn save_path() -> PathBuf {
+  std::env::current_exe()
+    .ok()
+    .and_then(|exe| exe.parent().map(|dir| dir.join(SAVE_FILE)))
+    .unwrap_or_else(|| PathBuf::from(SAVE_FILE))
+}
+
+// Where quick saves go, next to the executable unless set otherwise.
+pub struct SavePath(pub PathBuf);
+
+impl Default for SavePath {
+  fn default() -> Self {
+    Self(save_path())
+  }
+}
+
+pub fn save_game(path: &Path, state: &SaveState) -> Result<(), SaveError> {
+  let contents = ron::ser::to_string_pretty(state, ron::ser::PrettyConfig::default())?;
+  fs::write(path, contents)?;
+  Ok(())
+}
+
+pub fn load_game(path: &Path) -> Result<SaveState, SaveError> {
+  Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Only plain food is saved. Anything with a timer of its own (bonus food,
+// poison, power-ups) is left out and respawns as usual after a load.
+pub fn quick_save(
+  input: Res<Input<KeyCode>>,
+  path: Res<SavePath>,
+  segments: Res<SnakeSegments>,
+  score: Res<Score>,
+  lives: Res<Lives>,
+  base_interval: Res<BaseInterval>,
+  rng: Res<GameRng>,
+  heads: Query<&SnakeHead>,
+  positions: Query<&Position, With<SnakeSegment>>,
+  food: Query<&Position, (With<Food>, Without<BonusFood>)>,
+) {
+  if !input.just_pressed(QUICK_SAVE_KEY) { return; }
+  let mut snakes = Vec::new();
+  for &player in [Player::One, Player::Two].iter() {
+    let entities = match segments.get(player) {
+      Some(entities) if !entities.is_empty() => entities,
+      _ => continue,
+    };
+    let direction = match heads.get(entities[0]) {
+      Ok(head) => head.queued_direction(),
+      Err(_) => continue,
+    };
+    snakes.push(SavedSnake {
+      player,
+      segments: entities.iter().filter_map(|&entity| positions.get(entity).ok()).copied().collect(),
+      direction,
+    });
+  }
+  let rng_seed = rng.0.clone().gen();
+  let state = SaveState {
+    snakes,
+    food: food.iter().copied().collect(),
+    score: score.0,
+    lives: lives.0,
+    // Without boost or slow-mo, neither of which is saved.
+    movement_interval: base_interval.0.as_secs_f32(),
+    rng_seed,
+  };
+  match save_game(&path.0, &state) {
+    Ok(()) => info!("Game saved"),
+    Err(error) => warn!("Couldn't save game: {}", error),
+  }
+}
+
+// Clears the board of everything but the level itself and rebuilds the saved
+// snakes segment by segment, so `SnakeSegments` keeps them head first. Runs in
+// `PreUpdate`, so the new segments exist by the time anything moves them.
+pub fn quick_load(
+  mut commands: Commands,
+  input: Res<Input<KeyCode>>,
+  path: Res<SavePath>,
+  materials: Res<Materials>,
+  visual: Res<VisualConfig>,
+  growth: Res<FoodGrowth>,
+  mut segments: ResMut<SnakeSegments>,
+  mut score: ResMut<Score>,
+  mut lives: ResMut<Lives>,
+  mut base_interval: ResMut<BaseInterval>,
+  mut timer: ResMut<MovementTimer>,
+  mut boosting: ResMut<Boosting>,
+  mut slow_mo: ResMut<SlowMo>,
+  mut rng: ResMut<GameRng>,
+  board: Query<
+    Entity,
+    Or<(With<Food>, With<PoisonFood>, With<BigFood>, With<BigFoodPart>, With<PowerUp>)>,
+  >,
+) {
+  if !input.just_pressed(QUICK_LOAD_KEY) { return; }
+  let state = match load_game(&path.0) {
+    Ok(state) => state,
+    Err(error) => {
+      warn!("Couldn't load game: {}", error);
+      return;
+    }
+  };
+  for entity in board.iter() {
+    commands.entity(entity).despawn();
+  }
+  for &player in [Player::One, Player::Two].iter() {
+    for &entity in segments.get(player).unwrap_or_default() {
+      commands.entity(entity).despawn();
+    }
+  }
+  segments.clear();
+  for snake in state.snakes.iter().filter(|snake| !snake.segments.is_empty()) {
+    let entities = spawn_player_snake(
+      &mut commands,
+      &materials,
+      snake.player,
+      &snake.segments,
+      snake.direction,
+      &visual,
+    );
+    segments.insert(snake.player, entities);
+  }
+  for &position in state.food.iter() {
+    let food = spawn_food(&mut commands, materials.food_material.clone(), position);
+    commands.entity(food).insert(GrowthAmount(growth.0));
+  }
+  score.0 = state.score;
+  lives.0 = state.lives;
+  base_interval.0 = Duration::from_secs_f32(state.movement_interval);
+  timer.0.set_duration(base_interval.0);
+  timer.0.reset();
+  boosting.0 = false;
+  *slow_mo = SlowMo::default();
+  *rng = GameRng::from_seed(state.rng_seed);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bevy_snake_{}_{}", std::process::id(), name))
+  }
+
+  fn save_world(path: PathBuf) -> World {
+    let mut world = World::default();
+    world.insert_resource(Input::<KeyCode>::default());
+    world.insert_resource(SavePath(path));
+    world.insert_resource(Materials::default());
+    world.insert_resource(VisualConfig::default());
+    world.insert_resource(FoodGrowth::default());
+    world.insert_resource(SnakeSegments::default());
+    world.insert_resource(Score::default());
+    world.insert_resource(Lives::default());
+    world.insert_resource(BaseInterval::default());
+    world.insert_resource(MovementTimer::default());
+    world.insert_resource(Boosting::default());
+    world.insert_resource(SlowMo::default());
+    world.insert_resource(GameRng::from_seed(1));
+    world
+  }
+
+  fn press(world: &mut World, key: KeyCode) {
+    let mut input = world.get_resource_mut::<Input<KeyCode>>().unwrap();
+    *input = Input::default();
+    input.press(key);
+  }
+
+  fn snake_positions(world: &World) -> Vec<Position> {
+    let segments = world.get_resource::<SnakeSegments>().unwrap();
+    segments
+      .get(Player::One)
+      .unwrap_or_default()
+      .iter()
+      .map(|&entity| *world.get::<Position>(entity).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn a_bent_snake_loads_back_head_first() {
+    let path = temp_path("bent_snake.ron");
+    let mut world = save_world(path.clone());
+    // Up column 2, then along row 2 to the tail.
+    let bent = [Position::new(2, 3), Position::new(2, 2), Position::new(1, 2), Position::new(0, 2)];
+    let mut entities = Vec::new();
+    for (index, &position) in bent.iter().enumerate() {
+      let mut segment = world.spawn();
+      segment.insert(position).insert(SnakeSegment).insert(Player::One);
+      if index == 0 {
+        segment.insert(SnakeHead::new(Direction::Up));
+      }
+      entities.push(segment.id());
+    }
+    world.get_resource_mut::<SnakeSegments>().unwrap().insert(Player::One, entities.clone());
+
+    press(&mut world, QUICK_SAVE_KEY);
+    let mut save = SystemStage::single_threaded();
+    save.add_system(quick_save.system());
+    save.run(&mut world);
+    press(&mut world, QUICK_LOAD_KEY);
+    let mut load = SystemStage::single_threaded();
+    load.add_system(quick_load.system());
+    load.run(&mut world);
+
+    let segments = world.get_resource::<SnakeSegments>().unwrap();
+    let loaded = segments.get(Player::One).unwrap();
+    assert!(loaded.iter().all(|entity| !entities.contains(entity)));
+    assert!(world.get::<SnakeHead>(loaded[0]).is_some());
+    assert_eq!(snake_positions(&world), bent.to_vec());
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn a_missing_save_is_an_io_error() {
+    let result = load_game(&temp_path("missing_save.ron"));
+    assert!(matches!(result, Err(SaveError::Io(_))));
+  }
+
+  #[test]
+  fn a_malformed_save_is_a_format_error() {
+    let path = temp_path("malformed_save.ron");
+    fs::write(&path, "not a save").unwrap();
+    assert!(matches!(load_game(&path), Err(SaveError::Format(_))));
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::game::*;
+
+// The smallest the open part of the board gets, across its narrower side.
+pub const MIN_OPEN_SIZE: u32 = 3;
+
+// Every `interval` the outermost `thickness` rings of open tiles turn into
+// walls. Only there in the shrinking arena mode.
+pub struct ShrinkSchedule {
+  pub interval: Duration,
+  pub thickness: u32,
+  pub timer: Timer,
+  // How many rings have closed in so far this run.
+  pub rings: u32,
+}
+
+impl ShrinkSchedule {
+  pub fn new(interval: Duration, thickness: u32) -> Self {
+    Self { interval, thickness: thickness.max(1), timer: Timer::new(interval, true), rings: 0 }
+  }
+}
+
+// How many rings in from the nearest edge a tile is, 0 being the outermost.
+fn ring(position: &Position, arena: &ArenaSize) -> u32 {
+  let right = arena.width as i32 - 1 - position.x();
+  let top = arena.height as i32 - 1 - position.y();
+  position.x().min(position.y()).min(right).min(top).max(0) as u32
+}
+
+pub fn reset_shrink_schedule(schedule: Option<ResMut<ShrinkSchedule>>) {
+  if let Some(mut schedule) = schedule {
+    schedule.timer.reset();
+    schedule.rings = 0;
+  }
+}
+
+// New walls go through the same collision as any other wall from the next
+// tick on. A player's snake caught under one crashes straight away, food under
+// one is eaten up by it, and the AI is out the next time it moves. Big food
+// goes all at once as soon as any of its tiles is caught, so no part of it is
+// left behind that can't be finished. Runs after `snake_movement`, so a snake
+// is judged on where it ends up this step.
+pub fn shrink_arena(
+  mut commands: Commands,
+  countdown: Res<Countdown>,
+  arena: Res<ArenaSize>,
+  materials: Res<Materials>,
+  schedule: Option<ResMut<ShrinkSchedule>>,
+  walls: Query<&Position, With<Wall>>,
+  segments: Query<(&Position, &Player), With<SnakeSegment>>,
+  food: Query<(Entity, &Position), Or<(With<Food>, With<PoisonFood>, With<PowerUp>)>>,
+  big_food_parts: Query<(Entity, &Position, &BigFoodPart)>,
+  mut crash_writer: EventWriter<SnakeCrashed>,
+) {
+  let mut schedule = match schedule {
+    Some(schedule) => schedule,
+    None => return,
+  };
+  let step = Duration::from_secs_f64(SIMULATION_STEP);
+  if !countdown.0.finished() || !schedule.timer.tick(step).just_finished() { return; }
+  let max_rings = (arena.width.min(arena.height).saturating_sub(MIN_OPEN_SIZE)) / 2;
+  let closed = schedule.rings;
+  let rings = (closed + schedule.thickness).min(max_rings);
+  if rings == closed { return; }
+  let closing = |position: &Position| (closed..rings).contains(&ring(position, &arena));
+  let walled = walls.iter().copied().collect::<HashSet<Position>>();
+  for y in 0..arena.height as i32 {
+    for x in 0..arena.width as i32 {
+      let position = Position::new(x, y);
+      if closing(&position) && !walled.contains(&position) {
+        spawn_wall(&mut commands, materials.wall_material.clone(), position);
+      }
+    }
+  }
+  let caught = segments
+    .iter()
+    .filter(|(position, _)| closing(position))
+    .map(|(_, &player)| player)
+    .collect::<HashSet<Player>>();
+  for player in caught {
+    crash_writer.send(SnakeCrashed { player });
+  }
+  for (entity, position) in food.iter() {
+    if closing(position) {
+      commands.entity(entity).despawn();
+    }
+  }
+  let caught_big_food = big_food_parts
+    .iter()
+    .filter(|(_, position, _)| closing(position))
+    .map(|(_, _, part)| part.0)
+    .collect::<HashSet<Entity>>();
+  for (entity, _, part) in big_food_parts.iter() {
+    if caught_big_food.contains(&part.0) {
+      commands.entity(entity).despawn();
+    }
+  }
+  for big_food in caught_big_food {
+    commands.entity(big_food).despawn();
+  }
+  schedule.rings = rings;
+}
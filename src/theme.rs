@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy::render::pass::ClearColor;
+
+use crate::game::{Materials, MaterialsConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+  Classic,
+  Nokia,
+  HighContrast,
+}
+
+impl Theme {
+  pub fn next(&self) -> Self {
+    match self {
+      Self::Classic => Self::Nokia,
+      Self::Nokia => Self::HighContrast,
+      Self::HighContrast => Self::Classic,
+    }
+  }
+
+  // Only the colors that make up the board change. The rest, and all of them in
+  // `Classic`, are the configured colors in `base`.
+  pub fn materials(&self, base: &MaterialsConfig) -> MaterialsConfig {
+    match self {
+      Self::Classic => base.clone(),
+      Self::Nokia => MaterialsConfig {
+        head: Color::rgb(0.16, 0.2, 0.14),
+        segment: Color::rgb(0.26, 0.32, 0.24),
+        // An olive that still fits the screen, but can't be mistaken for the head.
+        food: Color::rgb(0.47, 0.55, 0.16),
+        wall: Color::rgb(0.36, 0.44, 0.33),
+        grid: Color::rgb(0.72, 0.88, 0.78),
+        ..base.clone()
+      },
+      Self::HighContrast => MaterialsConfig {
+        head: Color::WHITE,
+        segment: Color::rgb(1.0, 0.85, 0.0),
+        food: Color::rgb(0.0, 0.9, 1.0),
+        wall: Color::rgb(0.5, 0.5, 0.5),
+        grid: Color::rgb(0.15, 0.15, 0.15),
+        ..base.clone()
+      },
+    }
+  }
+
+  pub fn background(&self, base: Color) -> Color {
+    match self {
+      Self::Classic => base,
+      Self::Nokia => Color::rgb(0.78, 0.94, 0.85),
+      Self::HighContrast => Color::BLACK,
+    }
+  }
+}
+
+pub struct CurrentTheme(pub Theme);
+
+impl Default for CurrentTheme {
+  fn default() -> Self {
+    Self(Theme::Classic)
+  }
+}
+
+pub fn cycle_theme(input: Res<Input<KeyCode>>, mut theme: ResMut<CurrentTheme>) {
+  if input.just_pressed(KeyCode::T) {
+    theme.0 = theme.0.next();
+  }
+}
+
+// The starting colors come from `MaterialsConfig` and `ClearColor`, so nothing
+// is applied until the theme is actually changed. Those starting colors are
+// kept as the base every theme is built on, so `Classic` brings them back.
+pub fn apply_theme(
+  mut configured: Local<Option<(MaterialsConfig, Color)>>,
+  theme: Res<CurrentTheme>,
+  materials: Res<Materials>,
+  mut config: ResMut<MaterialsConfig>,
+  mut clear_color: ResMut<ClearColor>,
+  mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+  let (base, background) = configured.get_or_insert_with(|| (config.clone(), clear_color.0));
+  if !theme.is_changed() || theme.is_added() { return; }
+  *config = theme.0.materials(base);
+  materials.recolor(&config, &mut color_materials);
+  clear_color.0 = theme.0.background(*background);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn food_stands_out_in_every_theme() {
+    for &theme in [Theme::Classic, Theme::Nokia, Theme::HighContrast].iter() {
+      let materials = theme.materials(&MaterialsConfig::default());
+      for &color in [materials.head, materials.segment, materials.wall].iter() {
+        assert_ne!(materials.food, color, "{:?}", theme);
+      }
+    }
+  }
+
+  #[test]
+  fn themes_build_on_the_configured_colors() {
+    let base = MaterialsConfig { head: Color::PINK, poison: Color::PURPLE, ..Default::default() };
+    let classic = Theme::Classic.materials(&base);
+    assert_eq!((classic.head, classic.poison), (Color::PINK, Color::PURPLE));
+    let nokia = Theme::Nokia.materials(&base);
+    assert_ne!(nokia.head, Color::PINK);
+    assert_eq!(nokia.poison, Color::PURPLE);
+    assert_eq!(Theme::Classic.background(Color::NAVY), Color::NAVY);
+  }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::prelude::*;
+
+use crate::game::{steer_player_one, Direction, Player, SnakeHead};
+use crate::replay::ReplayMode;
+
+// Shorter swipes are taken for taps and ignored.
+pub const MIN_SWIPE_DISTANCE: f32 = 30.0;
+pub const DPAD_BUTTON_SIZE: f32 = 48.0;
+pub const DPAD_MARGIN: f32 = 10.0;
+
+// Whether the on-screen D-pad is shown. It's only on by default where there's
+// probably no keyboard.
+pub struct ShowTouchControls(pub bool);
+
+impl Default for ShowTouchControls {
+  fn default() -> Self {
+    Self(cfg!(any(target_os = "android", target_os = "ios", target_arch = "wasm32")))
+  }
+}
+
+pub struct DpadButton(pub Direction);
+
+// Where every finger currently on the screen first touched down.
+#[derive(Default)]
+pub struct SwipeStarts(pub HashMap<u64, Vec2>);
+
+// The direction of a swipe is whichever axis it moved further along. Touch
+// positions grow upwards, like the board.
+pub fn swipe_direction(start: Vec2, end: Vec2) -> Option<Direction> {
+  let delta = end - start;
+  if delta.length() < MIN_SWIPE_DISTANCE {
+    None
+  } else if delta.x.abs() > delta.y.abs() {
+    Some(if delta.x > 0.0 { Direction::Right } else { Direction::Left })
+  } else {
+    Some(if delta.y > 0.0 { Direction::Up } else { Direction::Down })
+  }
+}
+
+// Swipes steer player one through the same queue as the keyboard, which keeps
+// working alongside them.
+pub fn swipe_input(
+  mut touch_reader: EventReader<TouchInput>,
+  mut starts: ResMut<SwipeStarts>,
+  replay_mode: Res<ReplayMode>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+) {
+  for touch in touch_reader.iter() {
+    let start = match touch.phase {
+      TouchPhase::Started => {
+        starts.0.insert(touch.id, touch.position);
+        continue;
+      }
+      TouchPhase::Moved => continue,
+      TouchPhase::Cancelled => {
+        starts.0.remove(&touch.id);
+        continue;
+      }
+      TouchPhase::Ended => match starts.0.remove(&touch.id) {
+        Some(start) => start,
+        None => continue,
+      },
+    };
+    if *replay_mode == ReplayMode::Playback { continue; }
+    let direction = match swipe_direction(start, touch.position) {
+      Some(direction) => direction,
+      None => continue,
+    };
+    steer_player_one(&mut heads, direction);
+  }
+}
+
+// A plus-shaped pad in the bottom right corner, one button for each direction.
+pub fn spawn_touch_controls(
+  mut commands: Commands,
+  show: Res<ShowTouchControls>,
+  asset_server: Res<AssetServer>,
+  mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+  if !show.0 { return; }
+  let material = color_materials.add(Color::rgba(1.0, 1.0, 1.0, 0.2).into());
+  let font = asset_server.load("fonts/DejaVuSans.ttf");
+  let step = DPAD_BUTTON_SIZE + DPAD_MARGIN;
+  let buttons = [
+    (Direction::Up, "\u{2191}", DPAD_MARGIN + step * 2.0, DPAD_MARGIN + step),
+    (Direction::Down, "\u{2193}", DPAD_MARGIN, DPAD_MARGIN + step),
+    (Direction::Left, "\u{2190}", DPAD_MARGIN + step, DPAD_MARGIN + step * 2.0),
+    (Direction::Right, "\u{2192}", DPAD_MARGIN + step, DPAD_MARGIN),
+  ];
+  for &(direction, arrow, bottom, right) in buttons.iter() {
+    commands
+      .spawn_bundle(ButtonBundle {
+        style: Style {
+          size: Size::new(Val::Px(DPAD_BUTTON_SIZE), Val::Px(DPAD_BUTTON_SIZE)),
+          position_type: PositionType::Absolute,
+          position: Rect {
+            bottom: Val::Px(bottom),
+            right: Val::Px(right),
+            ..Default::default()
+          },
+          justify_content: JustifyContent::Center,
+          align_items: AlignItems::Center,
+          ..Default::default()
+        },
+        material: material.clone(),
+        ..Default::default()
+      })
+      .insert(DpadButton(direction))
+      .with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+          text: Text::with_section(
+            arrow,
+            TextStyle {
+              font: font.clone(),
+              font_size: 32.0,
+              color: Color::WHITE,
+            },
+            Default::default(),
+          ),
+          ..Default::default()
+        });
+      });
+  }
+}
+
+// Taps go through `queue_direction` like every other input, so the D-pad can't
+// reverse the snake either.
+pub fn dpad_input(
+  replay_mode: Res<ReplayMode>,
+  buttons: Query<(&Interaction, &DpadButton), Changed<Interaction>>,
+  mut heads: Query<(&mut SnakeHead, &Player)>,
+) {
+  if *replay_mode == ReplayMode::Playback { return; }
+  for (interaction, button) in buttons.iter() {
+    if *interaction == Interaction::Clicked {
+      steer_player_one(&mut heads, button.0);
+    }
+  }
+}
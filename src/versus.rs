@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::combo::Combo;
+use crate::game::*;
+
+// How many rounds a versus match is the best of.
+pub struct MatchLength(pub u32);
+
+impl Default for MatchLength {
+  fn default() -> Self {
+    Self(3)
+  }
+}
+
+impl MatchLength {
+  pub fn wins_needed(&self) -> u32 {
+    self.0 / 2 + 1
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundResult {
+  Won(Player),
+  // Both snakes crashed on the same tick, so the round is played again.
+  Draw,
+}
+
+// Sent when a versus round ends without deciding the match.
+pub struct RoundOver(pub RoundResult);
+
+#[derive(Default)]
+pub struct MatchState {
+  pub p1_wins: u32,
+  pub p2_wins: u32,
+  pub last_round: Option<RoundResult>,
+  pub winner: Option<Player>,
+}
+
+impl MatchState {
+  pub fn round(&self) -> u32 {
+    self.p1_wins + self.p2_wins + 1
+  }
+}
+
+// Each player's own points in versus. The shared `Score` still adds up both.
+#[derive(Default)]
+pub struct PlayerScores(pub HashMap<Player, u32>);
+
+impl PlayerScores {
+  pub fn get(&self, player: Player) -> u32 {
+    self.0.get(&player).copied().unwrap_or_default()
+  }
+}
+
+pub fn player_name(player: Player) -> &'static str {
+  match player {
+    Player::One => "Player One",
+    Player::Two => "Player Two",
+  }
+}
+
+pub fn reset_match(mut match_state: ResMut<MatchState>, mut scores: ResMut<PlayerScores>) {
+  *match_state = MatchState::default();
+  scores.0.clear();
+}
+
+pub fn player_scoring(
+  combo: Res<Combo>,
+  mut scores: ResMut<PlayerScores>,
+  mut score_reader: EventReader<ScoreEvent>,
+) {
+  for event in score_reader.iter() {
+    let score = scores.0.entry(event.player).or_default();
+    *score = event.apply(*score, combo.multiplier);
+  }
+}
+
+// The survivor takes the round. Everything that crashed on the same frame
+// counts together, so a head-on collision is a draw. Once the match is decided
+// the run ends like any other game over.
+pub fn versus_rounds(
+  play_mode: Res<PlayMode>,
+  match_length: Res<MatchLength>,
+  mut match_state: ResMut<MatchState>,
+  mut lives: ResMut<Lives>,
+  mut crash_reader: EventReader<SnakeCrashed>,
+  mut round_writer: EventWriter<RoundOver>,
+  mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+  if *play_mode != PlayMode::Versus { return; }
+  let crashed = crash_reader.iter().map(|event| event.player).collect::<HashSet<Player>>();
+  let result = match (crashed.contains(&Player::One), crashed.contains(&Player::Two)) {
+    (false, false) => return,
+    (true, true) => RoundResult::Draw,
+    (true, false) => RoundResult::Won(Player::Two),
+    (false, true) => RoundResult::Won(Player::One),
+  };
+  match result {
+    RoundResult::Won(Player::One) => match_state.p1_wins += 1,
+    RoundResult::Won(Player::Two) => match_state.p2_wins += 1,
+    RoundResult::Draw => {}
+  }
+  match_state.last_round = Some(result);
+  let wins_needed = match_length.wins_needed();
+  match_state.winner = if match_state.p1_wins >= wins_needed {
+    Some(Player::One)
+  } else if match_state.p2_wins >= wins_needed {
+    Some(Player::Two)
+  } else {
+    None
+  };
+  if match_state.winner.is_some() {
+    lives.0 = 0;
+    game_over_writer.send(GameOverEvent);
+  } else {
+    round_writer.send(RoundOver(result));
+  }
+}
@@ -0,0 +1,94 @@
+// Pieces shared by the headless tests. `Time` is replaced before every frame
+// so it never moves, which keeps the countdown and anything else still running
+// on real time still, and the simulation is stepped by hand through
+// `SimulationClock::advance`.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use bevy::app::AppBuilder;
+use bevy::asset::AssetPlugin;
+use bevy::core::CorePlugin;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::{ElementState, InputPlugin};
+use bevy::prelude::*;
+use bevy::window::WindowPlugin;
+
+use bevy_snake::game::*;
+
+// Plenty for one movement tick at the starting speed.
+pub const MAX_STEPS_PER_MOVE: u32 = 100;
+
+// Everything `SnakePlugin` needs short of a window and a renderer.
+pub fn headless_builder() -> AppBuilder {
+  let mut builder = App::build();
+  builder
+    .add_plugin(CorePlugin::default())
+    .add_plugin(AssetPlugin::default())
+    .add_plugin(InputPlugin::default())
+    .add_plugin(WindowPlugin { add_primary_window: false, exit_on_close: false })
+    .add_asset::<ColorMaterial>()
+    .add_asset::<AudioSource>()
+    .init_resource::<Audio>();
+  builder
+}
+
+pub fn frame(app: &mut App) {
+  app.world.insert_resource(Time::default());
+  app.update();
+}
+
+pub fn key(app: &mut App, key_code: KeyCode, state: ElementState) {
+  let mut events = app.world.get_resource_mut::<Events<KeyboardInput>>().unwrap();
+  events.send(KeyboardInput { scan_code: 0, key_code: Some(key_code), state });
+}
+
+pub fn press(app: &mut App, key_code: KeyCode) {
+  key(app, key_code, ElementState::Pressed);
+  frame(app);
+  key(app, key_code, ElementState::Released);
+}
+
+pub fn game_state(app: &App) -> GameState {
+  *app.world.get_resource::<State<GameState>>().unwrap().current()
+}
+
+// Presses Return on the menu and skips the countdown, which runs on real time.
+pub fn start_playing(app: &mut App) {
+  frame(app);
+  press(app, KeyCode::Return);
+  frame(app);
+  assert_eq!(game_state(app), GameState::Playing);
+  let mut countdown = app.world.get_resource_mut::<Countdown>().unwrap();
+  let remaining = countdown.0.duration();
+  countdown.0.tick(remaining + Duration::from_secs(1));
+}
+
+pub fn ticks(app: &App) -> u64 {
+  app.world.get_resource::<GameStats>().unwrap().ticks_survived
+}
+
+pub fn step(app: &mut App) {
+  app.world.get_resource_mut::<SimulationClock>().unwrap().advance(1);
+  frame(app);
+}
+
+// Steps the simulation until the snakes have moved once, and returns how many
+// steps that took.
+pub fn next_move(app: &mut App) -> u32 {
+  let before = ticks(app);
+  for steps in 1..=MAX_STEPS_PER_MOVE {
+    step(app);
+    if ticks(app) > before { return steps; }
+  }
+  panic!("The snake didn't move within {} steps", MAX_STEPS_PER_MOVE);
+}
+
+pub fn head_position(app: &mut App) -> Position {
+  let mut heads = app.world.query_filtered::<(&Position, &Player), With<SnakeHead>>();
+  heads
+    .iter(&app.world)
+    .find(|(_, player)| **player == Player::One)
+    .map(|(position, _)| *position)
+    .expect("Player one has no head")
+}
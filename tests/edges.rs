@@ -0,0 +1,36 @@
+// What ends up in `EdgeConfig` depending on what was inserted before the
+// plugin was added.
+
+mod common;
+
+use bevy_snake::game::*;
+use bevy_snake::SnakePlugin;
+
+use common::*;
+
+#[test]
+fn a_lone_wall_behavior_applies_to_every_edge() {
+  let mut builder = headless_builder();
+  builder.insert_resource(WallBehavior::Wrap).add_plugin(SnakePlugin::new());
+  let edges = builder.app.world.get_resource::<EdgeConfig>().unwrap();
+  assert!(*edges == EdgeConfig::all(WallBehavior::Wrap));
+}
+
+#[test]
+fn an_edge_config_beats_a_wall_behavior() {
+  let edges = EdgeConfig { left: WallBehavior::Wrap, ..EdgeConfig::default() };
+  let mut builder = headless_builder();
+  builder
+    .insert_resource(WallBehavior::Bounce)
+    .insert_resource(edges)
+    .add_plugin(SnakePlugin::new());
+  assert!(*builder.app.world.get_resource::<EdgeConfig>().unwrap() == edges);
+}
+
+#[test]
+fn without_either_every_edge_is_deadly() {
+  let mut builder = headless_builder();
+  builder.add_plugin(SnakePlugin::new());
+  let edges = builder.app.world.get_resource::<EdgeConfig>().unwrap();
+  assert!(*edges == EdgeConfig::all(WallBehavior::Die));
+}
@@ -0,0 +1,51 @@
+// Plays a short game start to finish without a window, steering with the same
+// key events a player would send.
+
+mod common;
+
+use bevy::prelude::*;
+
+use bevy_snake::combo::ComboConfig;
+use bevy_snake::game::*;
+use bevy_snake::level::LevelData;
+use bevy_snake::SnakePlugin;
+
+use common::*;
+
+fn headless_app() -> App {
+  let level = LevelData {
+    width: 8,
+    height: 8,
+    food: vec![Position::new(3, 5), Position::new(5, 6)],
+    ..Default::default()
+  };
+  let mut builder = headless_builder();
+  builder
+    // Only the level's food, and no combo bonus, so every point is accounted for.
+    .insert_resource(FoodCount(0))
+    .insert_resource(ComboConfig { window: 3.0, max_multiplier: 1 })
+    .add_plugin(SnakePlugin::new().with_seed(7).with_level(level));
+  builder.app
+}
+
+#[test]
+fn scripted_game_eats_both_food() {
+  let mut app = headless_app();
+  start_playing(&mut app);
+
+  // The snake starts on (3, 3) heading up, two segments long.
+  assert_eq!(head_position(&mut app), Position::new(3, 3));
+  next_move(&mut app);
+  next_move(&mut app);
+  assert_eq!(head_position(&mut app), Position::new(3, 5));
+  next_move(&mut app);
+  press(&mut app, KeyCode::Right);
+  next_move(&mut app);
+  next_move(&mut app);
+  next_move(&mut app);
+
+  assert_eq!(head_position(&mut app), Position::new(6, 6));
+  assert_eq!(app.world.get_resource::<Score>().unwrap().0, 2);
+  assert_eq!(app.world.get_resource::<SnakeSegments>().unwrap().len(Player::One), 4);
+  assert_eq!(game_state(&app), GameState::Playing);
+}
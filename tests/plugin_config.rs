@@ -0,0 +1,30 @@
+// Settings inserted before `SnakePlugin` is added are kept, unless the
+// plugin's builder is given one of its own.
+
+mod common;
+
+use bevy::prelude::*;
+
+use bevy_snake::game::*;
+use bevy_snake::SnakePlugin;
+
+use common::*;
+
+fn red_head() -> MaterialsConfig {
+  MaterialsConfig { head: Color::RED, ..Default::default() }
+}
+
+#[test]
+fn inserted_materials_config_is_kept() {
+  let mut builder = headless_builder();
+  builder.insert_resource(red_head()).add_plugin(SnakePlugin::new());
+  assert_eq!(builder.app.world.get_resource::<MaterialsConfig>().unwrap().head, Color::RED);
+}
+
+#[test]
+fn with_materials_beats_an_inserted_one() {
+  let blue_head = MaterialsConfig { head: Color::BLUE, ..Default::default() };
+  let mut builder = headless_builder();
+  builder.insert_resource(red_head()).add_plugin(SnakePlugin::new().with_materials(blue_head));
+  assert_eq!(builder.app.world.get_resource::<MaterialsConfig>().unwrap().head, Color::BLUE);
+}
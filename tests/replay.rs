@@ -0,0 +1,65 @@
+// Records a short game, then plays the recording back in a fresh app and
+// checks both ended up in the same place. Food is left to the spawner and the
+// snake boosts for a while, so the replay only matches if every random draw
+// and every timer lines up with the recording step for step.
+
+mod common;
+
+use bevy::input::ElementState;
+use bevy::prelude::*;
+
+use bevy_snake::game::*;
+use bevy_snake::level::LevelData;
+use bevy_snake::replay::Replay;
+use bevy_snake::SnakePlugin;
+
+use common::*;
+
+fn headless_app(plugin: SnakePlugin) -> App {
+  let level = LevelData { width: 10, height: 10, ..Default::default() };
+  let mut builder = headless_builder();
+  builder.add_plugin(plugin.with_level(level));
+  builder.app
+}
+
+// Player one's body head first, and every food on the board in row order.
+fn snapshot(app: &mut App) -> (Vec<Position>, Vec<Position>) {
+  let body = app.world.get_resource::<SnakeSegments>().unwrap().get(Player::One).unwrap().to_vec();
+  let body = body.iter().map(|&segment| *app.world.get::<Position>(segment).unwrap()).collect();
+  let mut food: Vec<Position> =
+    app.world.query_filtered::<&Position, With<Food>>().iter(&app.world).copied().collect();
+  food.sort_by_key(|position| (position.y(), position.x()));
+  (body, food)
+}
+
+#[test]
+fn playback_retraces_the_recording() {
+  let mut recording = headless_app(SnakePlugin::new().with_seed(3).with_replay_recording(true));
+  start_playing(&mut recording);
+  let mut steps = 0;
+  steps += next_move(&mut recording);
+  steps += next_move(&mut recording);
+  press(&mut recording, KeyCode::Right);
+  key(&mut recording, KeyCode::LShift, ElementState::Pressed);
+  for _ in 0..3 {
+    steps += next_move(&mut recording);
+  }
+  key(&mut recording, KeyCode::LShift, ElementState::Released);
+  press(&mut recording, KeyCode::Down);
+  steps += next_move(&mut recording);
+  steps += next_move(&mut recording);
+  press(&mut recording, KeyCode::Left);
+  steps += next_move(&mut recording);
+  assert_eq!(game_state(&recording), GameState::Playing);
+  let replay = recording.world.get_resource::<Replay>().unwrap().clone();
+  assert!(!replay.boost_toggles.is_empty());
+
+  let mut playback = headless_app(SnakePlugin::new().with_replay(replay));
+  start_playing(&mut playback);
+  for _ in 0..steps {
+    step(&mut playback);
+  }
+
+  assert_eq!(ticks(&playback), ticks(&recording));
+  assert_eq!(snapshot(&mut playback), snapshot(&mut recording));
+}